@@ -1,20 +1,66 @@
 use nom::{branch::permutation, bytes::complete::tag, character::complete::digit1, IResult};
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
+use std::fs;
 use std::io::{self, Read, Write};
+use std::thread;
+use std::time::Duration;
 
 type Result<T> = ::std::result::Result<T, Box<dyn Error>>;
 
-#[derive(Debug)]
+// how many arrangements the planner explores before giving up on a minimal plan.
+const SEARCH_LIMIT: usize = 50_000;
+
+// links a searched arrangement back to the one it came from.
+type Parent = Option<(Vec<Vec<char>>, Command)>;
+
+#[derive(Debug, Clone, Copy)]
 struct Command {
   move_amount: i32,
   from: usize,
   to: usize,
 }
 
-fn main() -> Result<()> {
-  let mut input = String::new();
-  io::stdin().read_to_string(&mut input)?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Crane {
+  CrateMover9000, // moves crates one at a time.
+  CrateMover9001, // moves multiple crates at once, keeping their order.
+}
+
+#[derive(Debug, Default)]
+struct Options {
+  test: bool,
+  replay: bool,
+  delay: Option<u64>,
+  step: usize,
+  crane: Option<Crane>,
+  snapshot: Option<String>,
+  resume: Option<String>,
+  at: Option<usize>,
+  verify: bool,
+  plan: Option<String>,
+}
 
+// the stacks part way through a move list, and what got them there. saved as a
+// `<crane> after <n> moves` line followed by the drawing.
+#[derive(Debug)]
+struct Snapshot {
+  crane: Crane,
+  steps: usize,
+  crates: Vec<Vec<char>>,
+}
+
+// wraps the crates with a journal of applied commands, so every move can be
+// undone (by moving the same amount back with the same crane) and redone.
+#[derive(Debug, Clone)]
+struct StackMachine {
+  crates: Vec<Vec<char>>,
+  crane: Crane,
+  journal: Vec<Command>,
+  redo_stack: Vec<Command>,
+}
+
+fn main() -> Result<()> {
   let input_crates = vec![
     vec!['N', 'S', 'D', 'C', 'V', 'Q', 'T'],
     vec!['M', 'F', 'V'],
@@ -27,13 +73,127 @@ fn main() -> Result<()> {
     vec!['M', 'S', 'D', 'J', 'R', 'Q', 'H', 'T'],
   ];
 
-  let mut test_crates = vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']];
+  let opts = parse_options(std::env::args().skip(1))?;
+  let snapshot = match &opts.resume {
+    Some(path) => Some(parse_snapshot(&fs::read_to_string(path)?)?),
+    None => None,
+  };
+  // a snapshot already decides the crane and how many moves are done.
+  let crane = match (&snapshot, opts.crane) {
+    (Some(snapshot), Some(crane)) if crane != snapshot.crane => {
+      return Err(format!("the snapshot was made with the {:?}", snapshot.crane).into())
+    }
+    (Some(snapshot), _) => Some(snapshot.crane),
+    (None, crane) => crane,
+  };
+  let (crates, skip) = match snapshot {
+    Some(snapshot) => (snapshot.crates, snapshot.steps),
+    None if opts.test => (test_crates(), 0),
+    None => (input_crates, 0),
+  };
+
+  if let Some(path) = &opts.plan {
+    let target = parse_drawing(&fs::read_to_string(path)?)?;
+    return plan(&crates, &target, crane.unwrap_or(Crane::CrateMover9000));
+  }
 
-  solve_part1(&input, &mut input_crates.clone())?;
-  solve_part2(&input, &mut input_crates.clone())?;
+  let mut input = String::new();
+  io::stdin().read_to_string(&mut input)?;
+
+  if opts.verify {
+    return verify_resume(&input, &crates);
+  }
+
+  if let Some(path) = &opts.snapshot {
+    let at = opts.at.ok_or("--snapshot needs --at <n>")?;
+    if at < skip {
+      return Err(format!("the snapshot is already {} moves in", skip).into());
+    }
+    let mut machine = StackMachine::new(crates, crane.unwrap_or(Crane::CrateMover9000));
+    for cmd in parse_commands(&input)?.into_iter().take(at).skip(skip) {
+      machine.apply(cmd);
+    }
+    fs::write(path, machine.save(skip) + "\n")?;
+    writeln!(
+      io::stdout(),
+      "saved snapshot after {} moves to {}",
+      skip + machine.steps(),
+      path
+    )?;
+    return Ok(());
+  }
+
+  // when resuming, the first moves are already part of the snapshot.
+  if let Some(at) = opts.at.filter(|&at| opts.resume.is_some() && at != skip) {
+    return Err(
+      format!(
+        "--at {} but the snapshot was taken after {} moves",
+        at, skip
+      )
+      .into(),
+    );
+  }
+  let input = input.lines().skip(skip).collect::<Vec<&str>>().join("\n");
+
+  if opts.replay {
+    let cranes = match crane {
+      Some(crane) => vec![crane],
+      None => vec![Crane::CrateMover9000, Crane::CrateMover9001],
+    };
+    for crane in cranes {
+      replay(&input, &mut crates.clone(), crane, &opts)?;
+    }
+    return Ok(());
+  }
+
+  // each part uses its own crane, so only one of them fits a chosen crane.
+  if crane != Some(Crane::CrateMover9001) {
+    solve_part1(&input, &mut crates.clone())?;
+  }
+  if crane != Some(Crane::CrateMover9000) {
+    solve_part2(&input, &mut crates.clone())?;
+  }
   Ok(())
 }
 
+// usage: day05 [--test] [--replay] [--crane 9000|9001] [--delay <ms>] [--step <n>]
+//              [--snapshot <file> --at <n>] [--resume <file>] [--verify]
+//              [--plan <target-drawing>]
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options> {
+  let mut opts = Options::default();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--test" => opts.test = true,
+      "--replay" => opts.replay = true,
+      "--delay" => opts.delay = Some(next_value(&mut args, &arg)?.parse()?),
+      "--step" => {
+        opts.replay = true;
+        opts.step = next_value(&mut args, &arg)?.parse()?;
+      }
+      "--crane" => {
+        opts.crane = match next_value(&mut args, &arg)?.as_str() {
+          "9000" => Some(Crane::CrateMover9000),
+          "9001" => Some(Crane::CrateMover9001),
+          other => return Err(format!("unknown crane model: {}", other).into()),
+        }
+      }
+      "--snapshot" => opts.snapshot = Some(next_value(&mut args, &arg)?),
+      "--resume" => opts.resume = Some(next_value(&mut args, &arg)?),
+      "--at" => opts.at = Some(next_value(&mut args, &arg)?.parse()?),
+      "--verify" => opts.verify = true,
+      "--plan" => opts.plan = Some(next_value(&mut args, &arg)?),
+      _ => return Err(format!("unknown option: {}", arg).into()),
+    }
+  }
+  Ok(opts)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+  args
+    .next()
+    .ok_or_else(|| format!("missing value for {}", flag).into())
+}
+
 // test creates.
 //     [D]
 // [N] [C]
@@ -54,13 +214,7 @@ fn main() -> Result<()> {
 fn solve_part1(input: &str, crates: &mut [Vec<char>]) -> Result<()> {
   input.lines().for_each(|curr| {
     let (_, cmd) = parse_command(curr).unwrap();
-
-    for _ in 0..cmd.move_amount {
-      crates[cmd.from]
-        .pop()
-        .map(|moved_item| crates[cmd.to].push(moved_item))
-        .unwrap();
-    }
+    apply_command(crates, &cmd, Crane::CrateMover9000);
   });
 
   let res = get_result(crates);
@@ -72,12 +226,7 @@ fn solve_part1(input: &str, crates: &mut [Vec<char>]) -> Result<()> {
 fn solve_part2(input: &str, crates: &mut [Vec<char>]) -> Result<()> {
   input.lines().for_each(|curr| {
     let (_, cmd) = parse_command(curr).unwrap();
-
-    let drain_end = (crates[cmd.from].len()) as i32;
-    let drain_from = (drain_end - cmd.move_amount) as usize;
-
-    let mut moved_items: Vec<char> = crates[cmd.from].drain(drain_from..).collect();
-    crates[cmd.to].append(&mut moved_items);
+    apply_command(crates, &cmd, Crane::CrateMover9001);
   });
 
   let res = get_result(crates);
@@ -86,6 +235,317 @@ fn solve_part2(input: &str, crates: &mut [Vec<char>]) -> Result<()> {
   Ok(())
 }
 
+// prints the stacks after every command, skipping frames before `opts.step`.
+fn replay(input: &str, crates: &mut [Vec<char>], crane: Crane, opts: &Options) -> Result<()> {
+  let mut out = io::stdout();
+  writeln!(out, "== {:?} ==", crane)?;
+
+  if opts.step == 0 {
+    writeln!(out, "initial\n{}", render_crates(crates))?;
+  }
+
+  for (i, (curr, cmd)) in input.lines().zip(parse_commands(input)?).enumerate() {
+    apply_command(crates, &cmd, crane);
+
+    let step = i + 1;
+    if step < opts.step {
+      continue;
+    }
+    writeln!(out, "step {}: {}\n{}", step, curr, render_crates(crates))?;
+    out.flush()?;
+    if let Some(ms) = opts.delay {
+      thread::sleep(Duration::from_millis(ms));
+    }
+  }
+
+  writeln!(out, "final\n{}", render_crates(crates))?;
+  Ok(())
+}
+
+impl StackMachine {
+  fn new(crates: Vec<Vec<char>>, crane: Crane) -> Self {
+    StackMachine {
+      crates,
+      crane,
+      journal: vec![],
+      redo_stack: vec![],
+    }
+  }
+
+  fn steps(&self) -> usize {
+    self.journal.len()
+  }
+
+  fn apply(&mut self, cmd: Command) {
+    apply_command(&mut self.crates, &cmd, self.crane);
+    self.journal.push(cmd);
+    self.redo_stack.clear();
+  }
+
+  fn undo(&mut self) -> bool {
+    match self.journal.pop() {
+      Some(cmd) => {
+        apply_command(&mut self.crates, &invert_command(&cmd), self.crane);
+        self.redo_stack.push(cmd);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn redo(&mut self) -> bool {
+    match self.redo_stack.pop() {
+      Some(cmd) => {
+        apply_command(&mut self.crates, &cmd, self.crane);
+        self.journal.push(cmd);
+        true
+      }
+      None => false,
+    }
+  }
+
+  // a checkpoint is just the journal length at the time it was taken.
+  fn checkpoint(&self) -> usize {
+    self.steps()
+  }
+
+  fn rollback(&mut self, checkpoint: usize) {
+    while self.steps() > checkpoint && self.undo() {}
+  }
+
+  fn snapshot(&self) -> String {
+    render_crates(&self.crates)
+  }
+
+  // the snapshot file contents, `done` moves were applied before this machine
+  // started.
+  fn save(&self, done: usize) -> String {
+    format!(
+      "{:?} after {} moves\n{}",
+      self.crane,
+      done + self.steps(),
+      self.snapshot()
+    )
+  }
+}
+
+// resuming from a snapshot taken at any step must end up with the same stacks
+// as running the whole move list, and undoing everything must get back to the start.
+fn verify_resume(input: &str, crates: &[Vec<char>]) -> Result<()> {
+  let commands = parse_commands(input)?;
+
+  for crane in [Crane::CrateMover9000, Crane::CrateMover9001] {
+    let mut full = StackMachine::new(crates.to_vec(), crane);
+    commands.iter().for_each(|cmd| full.apply(*cmd));
+
+    for at in 0..=commands.len() {
+      let mut machine = StackMachine::new(crates.to_vec(), crane);
+      commands.iter().take(at).for_each(|cmd| machine.apply(*cmd));
+
+      let mut resumed = StackMachine::new(parse_drawing(&machine.snapshot())?, crane);
+      commands.iter().skip(at).for_each(|cmd| resumed.apply(*cmd));
+      if resumed.crates != full.crates {
+        return Err(format!("{:?}: resuming at move {} diverged", crane, at).into());
+      }
+    }
+
+    let mut machine = StackMachine::new(crates.to_vec(), crane);
+    commands
+      .iter()
+      .take(commands.len() / 2)
+      .for_each(|cmd| machine.apply(*cmd));
+    let (checkpoint, halfway) = (machine.checkpoint(), machine.snapshot());
+    commands
+      .iter()
+      .skip(checkpoint)
+      .for_each(|cmd| machine.apply(*cmd));
+    machine.rollback(checkpoint);
+    if machine.snapshot() != halfway {
+      return Err(format!("{:?}: rolling back to move {} failed", crane, checkpoint).into());
+    }
+
+    machine.rollback(0);
+    if machine.crates != crates {
+      return Err(format!("{:?}: undoing every move did not restore the start", crane).into());
+    }
+    while machine.redo() {}
+    if machine.crates != full.crates {
+      return Err(format!("{:?}: redoing every move did not restore the end", crane).into());
+    }
+  }
+
+  writeln!(io::stdout(), "ok")?;
+  Ok(())
+}
+
+// prints a move list that turns `start` into `target`, using the same syntax as the puzzle input.
+fn plan(start: &[Vec<char>], target: &[Vec<char>], crane: Crane) -> Result<()> {
+  if start.len() != target.len() {
+    return Err(
+      format!(
+        "expected {} stacks, target has {}",
+        start.len(),
+        target.len()
+      )
+      .into(),
+    );
+  }
+  let mut start_crates: Vec<char> = start.concat();
+  let mut target_crates: Vec<char> = target.concat();
+  start_crates.sort_unstable();
+  target_crates.sort_unstable();
+  if start_crates != target_crates {
+    return Err("start and target do not hold the same crates".into());
+  }
+
+  let (commands, method) = match search_moves(start, target, crane) {
+    Some(commands) => (commands, "minimal"),
+    None => (greedy_moves(start, target, crane)?, "greedy"),
+  };
+  let program = commands
+    .iter()
+    .map(format_command)
+    .collect::<Vec<String>>()
+    .join("\n");
+
+  // the program has to survive the same parser and crane as the puzzle input.
+  let mut crates = start.to_vec();
+  for cmd in parse_commands(&program)? {
+    apply_command(&mut crates, &cmd, crane);
+  }
+  if crates != target {
+    return Err("planned moves do not reach the target".into());
+  }
+
+  if !program.is_empty() {
+    writeln!(io::stdout(), "{}", program)?;
+  }
+  writeln!(
+    io::stderr(),
+    "{:?}: {} moves ({})",
+    crane,
+    commands.len(),
+    method
+  )?;
+  Ok(())
+}
+
+// breadth-first search over arrangements, so the first hit uses the fewest moves.
+// gives up (returns `None`) once more than `SEARCH_LIMIT` arrangements were seen.
+fn search_moves(start: &[Vec<char>], target: &[Vec<char>], crane: Crane) -> Option<Vec<Command>> {
+  let mut parents: HashMap<Vec<Vec<char>>, Parent> = HashMap::new();
+  let mut queue = VecDeque::new();
+  parents.insert(start.to_vec(), None);
+  queue.push_back(start.to_vec());
+
+  while let Some(crates) = queue.pop_front() {
+    if crates == target {
+      let mut commands = vec![];
+      let mut curr = crates;
+      while let Some(Some((prev, cmd))) = parents.get(&curr) {
+        commands.push(*cmd);
+        curr = prev.clone();
+      }
+      commands.reverse();
+      return Some(commands);
+    }
+
+    for from in 0..crates.len() {
+      for to in (0..crates.len()).filter(|&to| to != from) {
+        for move_amount in 1..=crates[from].len() as i32 {
+          let cmd = Command {
+            move_amount,
+            from,
+            to,
+          };
+          let mut next = crates.clone();
+          apply_command(&mut next, &cmd, crane);
+          if parents.contains_key(&next) {
+            continue;
+          }
+          if parents.len() >= SEARCH_LIMIT {
+            return None;
+          }
+          parents.insert(next.clone(), Some((crates.clone(), cmd)));
+          queue.push_back(next);
+        }
+      }
+    }
+  }
+
+  None
+}
+
+// builds the target bottom-up one crate at a time: clear whatever sits on the
+// stack being built, dig the next wanted crate out of another stack and move it over.
+// needs a third stack to park crates on, but works for any size.
+fn greedy_moves(start: &[Vec<char>], target: &[Vec<char>], crane: Crane) -> Result<Vec<Command>> {
+  if start.len() < 3 {
+    return Err("too many crates to search and fewer than 3 stacks to plan with".into());
+  }
+
+  let mut crates = start.to_vec();
+  let mut commands: Vec<Command> = vec![];
+  let mut move_one = |crates: &mut Vec<Vec<char>>, from: usize, to: usize| {
+    let cmd = Command {
+      move_amount: 1,
+      from,
+      to,
+    };
+    apply_command(crates, &cmd, crane);
+    commands.push(cmd);
+  };
+  let settled = |crates: &[Vec<char>], i: usize| {
+    crates[i]
+      .iter()
+      .zip(&target[i])
+      .take_while(|(a, b)| a == b)
+      .count()
+  };
+
+  while let Some(i) = (0..crates.len()).find(|&i| settled(&crates, i) < target[i].len()) {
+    let park = (0..crates.len()).find(|&k| k != i).unwrap();
+    while crates[i].len() > settled(&crates, i) {
+      move_one(&mut crates, i, park);
+    }
+
+    let wanted = target[i][settled(&crates, i)];
+    let (j, pos) = (0..crates.len())
+      .filter(|&j| j != i)
+      .filter_map(|j| {
+        let unsettled = settled(&crates, j);
+        crates[j]
+          .iter()
+          .rposition(|&c| c == wanted)
+          .filter(|&pos| pos >= unsettled)
+          .map(|pos| (j, pos))
+      })
+      .min_by_key(|&(j, pos)| crates[j].len() - pos)
+      .ok_or_else(|| format!("no spare crate {} left to move", wanted))?;
+
+    let park = (0..crates.len()).find(|&k| k != i && k != j).unwrap();
+    while crates[j].len() > pos + 1 {
+      move_one(&mut crates, j, park);
+    }
+    move_one(&mut crates, j, i);
+  }
+
+  // the 9000 moves crates one at a time anyway, so repeated single moves can be joined.
+  if let Crane::CrateMover9000 = crane {
+    commands = commands
+      .into_iter()
+      .fold(vec![], |mut acc: Vec<Command>, cmd| {
+        match acc.last_mut() {
+          Some(last) if last.from == cmd.from && last.to == cmd.to => last.move_amount += 1,
+          _ => acc.push(cmd),
+        }
+        acc
+      });
+  }
+
+  Ok(commands)
+}
+
 // ---------------------- UTILS -------------------------
 
 fn parse_command(input: &str) -> IResult<&str, Command> {
@@ -109,6 +569,129 @@ fn parse_command(input: &str) -> IResult<&str, Command> {
   ))
 }
 
+fn apply_command(crates: &mut [Vec<char>], cmd: &Command, crane: Crane) {
+  match crane {
+    Crane::CrateMover9000 => {
+      for _ in 0..cmd.move_amount {
+        crates[cmd.from]
+          .pop()
+          .map(|moved_item| crates[cmd.to].push(moved_item))
+          .unwrap();
+      }
+    }
+    Crane::CrateMover9001 => {
+      let drain_end = (crates[cmd.from].len()) as i32;
+      let drain_from = (drain_end - cmd.move_amount) as usize;
+
+      let mut moved_items: Vec<char> = crates[cmd.from].drain(drain_from..).collect();
+      crates[cmd.to].append(&mut moved_items);
+    }
+  }
+}
+
+// draws the stacks the same way as the puzzle input, eg.
+//     [D]
+// [N] [C]
+// [Z] [M] [P]
+//  1   2   3
+fn render_crates(crates: &[Vec<char>]) -> String {
+  let height = crates.iter().map(|stack| stack.len()).max().unwrap_or(0);
+  let mut lines: Vec<String> = (0..height)
+    .rev()
+    .map(|row| {
+      let line = crates
+        .iter()
+        .map(|stack| match stack.get(row) {
+          Some(c) => format!("[{}]", c),
+          None => "   ".to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+      line.trim_end().to_string()
+    })
+    .collect();
+
+  let labels = (1..=crates.len())
+    .map(|n| format!(" {} ", n))
+    .collect::<Vec<String>>()
+    .join(" ");
+  lines.push(labels.trim_end().to_string());
+  lines.join("\n")
+}
+
+// moving the same amount back with the same crane restores the previous order.
+fn invert_command(cmd: &Command) -> Command {
+  Command {
+    move_amount: cmd.move_amount,
+    from: cmd.to,
+    to: cmd.from,
+  }
+}
+
+fn format_command(cmd: &Command) -> String {
+  format!(
+    "move {} from {} to {}",
+    cmd.move_amount,
+    cmd.from + 1,
+    cmd.to + 1
+  )
+}
+
+fn parse_commands(input: &str) -> Result<Vec<Command>> {
+  input
+    .lines()
+    .map(|curr| {
+      parse_command(curr)
+        .map(|(_, cmd)| cmd)
+        .map_err(|e| e.to_string().into())
+    })
+    .collect()
+}
+
+// reads back the output of `render_crates`, stack labels on the last line.
+fn parse_snapshot(text: &str) -> Result<Snapshot> {
+  let (header, drawing) = text.split_once('\n').ok_or("empty snapshot")?;
+  let (crane, steps) = match header.split_whitespace().collect::<Vec<_>>()[..] {
+    [crane, "after", steps, "moves"] => (crane, steps),
+    _ => return Err(format!("not a snapshot header: {}", header).into()),
+  };
+  let crane = match crane {
+    "CrateMover9000" => Crane::CrateMover9000,
+    "CrateMover9001" => Crane::CrateMover9001,
+    other => return Err(format!("unknown crane: {}", other).into()),
+  };
+  Ok(Snapshot {
+    crane,
+    steps: steps.parse()?,
+    crates: parse_drawing(drawing)?,
+  })
+}
+
+fn parse_drawing(drawing: &str) -> Result<Vec<Vec<char>>> {
+  let mut lines: Vec<&str> = drawing.lines().filter(|l| !l.trim().is_empty()).collect();
+  let labels = lines.pop().ok_or("empty drawing")?;
+  let mut crates = vec![vec![]; labels.split_whitespace().count()];
+
+  for line in lines.iter().rev() {
+    for (i, c) in line.chars().skip(1).step_by(4).enumerate() {
+      if c == ' ' {
+        continue;
+      }
+      crates
+        .get_mut(i)
+        .ok_or_else(|| format!("crate outside of the labelled stacks: {}", line))?
+        .push(c);
+    }
+  }
+
+  Ok(crates)
+}
+
+// the stacks from the puzzle's example.
+fn test_crates() -> Vec<Vec<char>> {
+  vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']]
+}
+
 fn get_result(crates: &mut [Vec<char>]) -> String {
   let res = crates.iter_mut().fold(String::new(), |mut acc, curr| {
     let new_char = curr.pop().unwrap();
@@ -120,3 +703,45 @@ fn get_result(crates: &mut [Vec<char>]) -> String {
 }
 
 // ---------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLE: &str = include_str!("../input/test.txt");
+
+  #[test]
+  fn resume_undo_redo_and_rollback() {
+    verify_resume(SAMPLE, &test_crates()).unwrap();
+  }
+
+  // a snapshot file taken after any move resumes to the same answer, with the
+  // crane and move count it was saved with.
+  #[test]
+  fn snapshot_files_resume() {
+    let commands = parse_commands(SAMPLE).unwrap();
+    let path = std::env::temp_dir().join(format!("day05-snapshot-{}", std::process::id()));
+    for (crane, top) in [
+      (Crane::CrateMover9000, "CMZ"),
+      (Crane::CrateMover9001, "MCD"),
+    ] {
+      for at in 0..=commands.len() {
+        let mut machine = StackMachine::new(test_crates(), crane);
+        commands.iter().take(at).for_each(|cmd| machine.apply(*cmd));
+        fs::write(&path, machine.save(0) + "\n").unwrap();
+
+        let snapshot = parse_snapshot(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!((snapshot.crane, snapshot.steps), (crane, at));
+        assert_eq!(snapshot.crates, machine.crates);
+
+        let mut resumed = StackMachine::new(snapshot.crates, snapshot.crane);
+        commands
+          .iter()
+          .skip(snapshot.steps)
+          .for_each(|cmd| resumed.apply(*cmd));
+        assert_eq!(get_result(&mut resumed.crates), top);
+      }
+    }
+    fs::remove_file(&path).unwrap();
+  }
+}
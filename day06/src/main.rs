@@ -1,51 +1,576 @@
-use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::error::Error;
-use std::hash::Hash;
 use std::io::{self, Read, Write};
-use std::str::from_utf8;
 
 type Result<T> = ::std::result::Result<T, Box<dyn Error>>;
 
+const PACKET_MARKER_LEN: usize = 4;
+const MESSAGE_MARKER_LEN: usize = 14;
+const CHUNK_SIZE: usize = 4096;
+
+#[derive(Debug)]
+struct Options {
+  marker_len: Option<usize>,
+  lines: bool,
+  frames: bool,
+  packet_len: usize,
+  message_len: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+  StartOfPacket,
+  StartOfMessage,
+  Custom(usize), // reported at every position, not just the first one.
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Event {
+  stream: usize,
+  marker: Marker,
+  offset: usize, // characters processed when the marker completes.
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+  Packet,
+  Message,
+}
+
+#[derive(Debug)]
+struct Frame {
+  stream: usize,
+  kind: FrameKind,
+  offset: usize, // index of the first payload byte in the stream.
+  data: Vec<u8>,
+}
+
+// anything that turns a stream of bytes into items, see `decode`.
+trait ByteSink {
+  type Item;
+  fn push(&mut self, byte: u8) -> Vec<Self::Item>;
+  fn end_stream(&mut self) -> Vec<Self::Item>;
+}
+
+// the last `marker_len` symbols of a stream, with a count per byte so checking
+// whether they are all distinct doesn't need to look at the whole window.
+#[derive(Debug)]
+struct MarkerWindow {
+  marker: Marker,
+  marker_len: usize,
+  window: VecDeque<u8>,
+  counts: [usize; 256],
+  distinct: usize,
+  found: bool,
+}
+
+#[derive(Debug)]
+struct StreamDecoder {
+  windows: Vec<MarkerWindow>,
+  stream: usize,
+  offset: usize,
+}
+
+// splits a stream at every marker of one kind: each marker opens a frame that
+// runs up to the start of the next marker. a frame always holds at least one
+// byte, so the next marker is only looked for from the second byte after a
+// boundary, from scratch as markers never overlap.
+#[derive(Debug)]
+struct Framer {
+  kind: FrameKind,
+  window: MarkerWindow,
+  started: bool, // nothing before the first marker belongs to a frame.
+  data: Vec<u8>,
+  frame_offset: usize,
+  stream: usize,
+  offset: usize,
+}
+
+// frames messages, then packets inside each message's payload, so a packet
+// never straddles a message boundary and bytes before the first message
+// aren't framed at all. a message is reported before its packets.
+#[derive(Debug)]
+struct LayeredFramer {
+  messages: Framer,
+  packet_len: usize,
+}
+
 fn main() -> Result<()> {
-  let mut input = String::new();
-  io::stdin().read_to_string(&mut input)?;
+  let opts = parse_options(std::env::args().skip(1))?;
+
+  if opts.frames {
+    return report_frames(&opts);
+  }
+
+  let markers = match opts.marker_len {
+    Some(marker_len) => vec![(Marker::Custom(marker_len), marker_len)],
+    None => vec![
+      (Marker::StartOfPacket, opts.packet_len),
+      (Marker::StartOfMessage, opts.message_len),
+    ],
+  };
+  let mut decoder = StreamDecoder::new(&markers);
+
+  if opts.lines || opts.marker_len.is_some() {
+    return report_events(&mut decoder, &opts);
+  }
 
-  solve_part1(&input)?;
-  solve_part2(&input)?;
+  let mut events = vec![];
+  decode(io::stdin().lock(), &mut decoder, false, |event| {
+    events.push(event);
+    Ok(())
+  })?;
+
+  solve_part1(&events)?;
+  solve_part2(&events)?;
 
   Ok(())
 }
 
-fn solve_part1(input: &str) -> Result<()> {
-  let steps = 4;
-  let chars = input.as_bytes();
-  let signal = chars
-    .windows(steps)
-    .enumerate()
-    .find(|(_, slice)| {
-      let set = slice.iter().collect::<HashSet<&u8>>();
-      slice.len() == set.len()
-    })
-    .unwrap();
-  let res = signal.0 + steps;
+// usage: day06 [--marker-len <n>] [--lines] [--frames] [--packet-len <n>] [--message-len <n>]
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options> {
+  let mut opts = Options {
+    marker_len: None,
+    lines: false,
+    frames: false,
+    packet_len: PACKET_MARKER_LEN,
+    message_len: MESSAGE_MARKER_LEN,
+  };
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--marker-len" => opts.marker_len = Some(next_value(&mut args, &arg)?.parse()?),
+      "--packet-len" => opts.packet_len = next_value(&mut args, &arg)?.parse()?,
+      "--message-len" => opts.message_len = next_value(&mut args, &arg)?.parse()?,
+      "--lines" => opts.lines = true,
+      "--frames" => opts.frames = true,
+      _ => return Err(format!("unknown option: {}", arg).into()),
+    }
+  }
+  Ok(opts)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+  args
+    .next()
+    .ok_or_else(|| format!("missing value for {}", flag).into())
+}
+
+fn solve_part1(events: &[Event]) -> Result<()> {
+  let res = first_offset(events, Marker::StartOfPacket).ok_or("no start-of-packet marker found")?;
 
   writeln!(io::stdout(), "{}", res)?;
   Ok(())
 }
 
-fn solve_part2(input: &str) -> Result<()> {
-  let steps = 14;
-  let chars = input.as_bytes();
-  let signal = chars
-    .windows(steps)
-    .enumerate()
-    .find(|(_, slice)| {
-      let set = slice.iter().collect::<HashSet<&u8>>();
-      slice.len() == set.len()
-    })
-    .unwrap();
-  let res = signal.0 + steps;
+fn solve_part2(events: &[Event]) -> Result<()> {
+  let res =
+    first_offset(events, Marker::StartOfMessage).ok_or("no start-of-message marker found")?;
 
   writeln!(io::stdout(), "{}", res)?;
   Ok(())
 }
+
+// prints every event as soon as the decoder finds it, one stream per line with `--lines`.
+fn report_events(decoder: &mut StreamDecoder, opts: &Options) -> Result<()> {
+  let mut out = io::stdout();
+  let mut seen = 0;
+
+  decode(io::stdin().lock(), decoder, opts.lines, |event| {
+    seen += 1;
+    if opts.lines {
+      write!(out, "line {}: ", event.stream + 1)?;
+    }
+    writeln!(out, "{} at {}", marker_name(event.marker), event.offset)?;
+    Ok(())
+  })?;
+
+  if seen == 0 {
+    writeln!(out, "no marker found")?;
+  }
+  Ok(())
+}
+
+fn report_frames(opts: &Options) -> Result<()> {
+  let mut out = io::stdout();
+  let mut framer = LayeredFramer::new(opts.packet_len, opts.message_len);
+
+  decode(io::stdin().lock(), &mut framer, opts.lines, |frame| {
+    if opts.lines {
+      write!(out, "line {}: ", frame.stream + 1)?;
+    }
+    if frame.kind == FrameKind::Packet {
+      write!(out, "  ")?;
+    }
+    writeln!(
+      out,
+      "{:?} at {} len {}: {}",
+      frame.kind,
+      frame.offset,
+      frame.data.len(),
+      String::from_utf8_lossy(&frame.data)
+    )?;
+    Ok(())
+  })
+}
+
+// ---------------------- UTILS -------------------------
+
+// reads `reader` in chunks, so only the current marker windows are kept in memory.
+// with `per_line` every line is decoded as a separate stream, otherwise line
+// breaks are skipped and the whole input is one stream.
+fn decode<R: Read, S: ByteSink>(
+  mut reader: R,
+  sink: &mut S,
+  per_line: bool,
+  mut on_item: impl FnMut(S::Item) -> Result<()>,
+) -> Result<()> {
+  let mut buf = [0u8; CHUNK_SIZE];
+
+  loop {
+    let n = match reader.read(&mut buf) {
+      Ok(0) => break,
+      Ok(n) => n,
+      Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+      Err(e) => return Err(e.into()),
+    };
+
+    for &byte in &buf[..n] {
+      let items = match byte {
+        b'\n' if per_line => sink.end_stream(),
+        b'\n' | b'\r' => vec![],
+        _ => sink.push(byte),
+      };
+      for item in items {
+        on_item(item)?;
+      }
+    }
+  }
+
+  for item in sink.end_stream() {
+    on_item(item)?;
+  }
+  Ok(())
+}
+
+fn first_offset(events: &[Event], marker: Marker) -> Option<usize> {
+  events
+    .iter()
+    .find(|event| event.marker == marker)
+    .map(|event| event.offset)
+}
+
+fn marker_name(marker: Marker) -> String {
+  match marker {
+    Marker::StartOfPacket => "start-of-packet".to_string(),
+    Marker::StartOfMessage => "start-of-message".to_string(),
+    Marker::Custom(len) => format!("marker({})", len),
+  }
+}
+
+impl MarkerWindow {
+  fn new(marker: Marker, marker_len: usize) -> Self {
+    MarkerWindow {
+      marker,
+      marker_len,
+      window: VecDeque::with_capacity(marker_len),
+      counts: [0; 256],
+      distinct: 0,
+      found: false,
+    }
+  }
+
+  // returns true when the window now holds `marker_len` distinct symbols.
+  fn push(&mut self, byte: u8) -> bool {
+    let marker_len = self.marker_len;
+    if marker_len == 0 {
+      return false;
+    }
+
+    if self.window.len() == marker_len {
+      let leaving = self.window.pop_front().unwrap() as usize;
+      self.counts[leaving] -= 1;
+      if self.counts[leaving] == 0 {
+        self.distinct -= 1;
+      }
+    }
+
+    if self.counts[byte as usize] == 0 {
+      self.distinct += 1;
+    }
+    self.counts[byte as usize] += 1;
+    self.window.push_back(byte);
+
+    self.distinct == marker_len
+  }
+}
+
+impl StreamDecoder {
+  fn new(markers: &[(Marker, usize)]) -> Self {
+    StreamDecoder {
+      windows: markers
+        .iter()
+        .map(|(marker, len)| MarkerWindow::new(*marker, *len))
+        .collect(),
+      stream: 0,
+      offset: 0,
+    }
+  }
+}
+
+impl ByteSink for StreamDecoder {
+  type Item = Event;
+
+  fn push(&mut self, byte: u8) -> Vec<Event> {
+    self.offset += 1;
+    let (stream, offset) = (self.stream, self.offset);
+
+    self
+      .windows
+      .iter_mut()
+      .filter_map(|window| {
+        let complete = window.push(byte);
+        // start-of markers only count once per stream.
+        let report = match window.marker {
+          Marker::Custom(_) => complete,
+          _ => complete && !window.found,
+        };
+        window.found |= complete;
+        report.then_some(Event {
+          stream,
+          marker: window.marker,
+          offset,
+        })
+      })
+      .collect()
+  }
+
+  fn end_stream(&mut self) -> Vec<Event> {
+    self.windows = self
+      .windows
+      .iter()
+      .map(|window| MarkerWindow::new(window.marker, window.marker_len))
+      .collect();
+    self.stream += 1;
+    self.offset = 0;
+    vec![]
+  }
+}
+
+impl Framer {
+  fn new(kind: FrameKind, marker_len: usize) -> Self {
+    let marker = match kind {
+      FrameKind::Packet => Marker::StartOfPacket,
+      FrameKind::Message => Marker::StartOfMessage,
+    };
+    Framer {
+      kind,
+      window: MarkerWindow::new(marker, marker_len),
+      started: false,
+      data: vec![],
+      frame_offset: 0,
+      stream: 0,
+      offset: 0,
+    }
+  }
+
+  fn take_frame(&mut self) -> Frame {
+    Frame {
+      stream: self.stream,
+      kind: self.kind,
+      offset: self.frame_offset,
+      data: std::mem::take(&mut self.data),
+    }
+  }
+}
+
+impl ByteSink for Framer {
+  type Item = Frame;
+
+  fn push(&mut self, byte: u8) -> Vec<Frame> {
+    self.offset += 1;
+    self.data.push(byte);
+    if self.started && self.data.len() == 1 {
+      return vec![];
+    }
+    if !self.window.push(byte) {
+      return vec![];
+    }
+
+    // the marker that just completed is not part of the frame it closes.
+    let marker_len = self.window.marker_len;
+    self.data.truncate(self.data.len() - marker_len);
+    let frames = match self.started {
+      true => vec![self.take_frame()],
+      false => vec![],
+    };
+
+    self.data.clear();
+    self.started = true;
+    self.frame_offset = self.offset;
+    self.window = MarkerWindow::new(self.window.marker, marker_len);
+    frames
+  }
+
+  // whatever follows the last marker is still reported, unterminated.
+  fn end_stream(&mut self) -> Vec<Frame> {
+    let frames = match self.started && !self.data.is_empty() {
+      true => vec![self.take_frame()],
+      false => vec![],
+    };
+    *self = Framer {
+      stream: self.stream + 1,
+      ..Framer::new(self.kind, self.window.marker_len)
+    };
+    frames
+  }
+}
+
+impl LayeredFramer {
+  fn new(packet_len: usize, message_len: usize) -> Self {
+    LayeredFramer {
+      messages: Framer::new(FrameKind::Message, message_len),
+      packet_len,
+    }
+  }
+
+  // each message's payload is framed as a stream of its own, packet offsets
+  // are moved back into the stream the message came from.
+  fn with_packets(&self, messages: Vec<Frame>) -> Vec<Frame> {
+    let mut frames = vec![];
+    for message in messages {
+      let mut packets = Framer::new(FrameKind::Packet, self.packet_len);
+      let mut inner: Vec<Frame> = message
+        .data
+        .iter()
+        .flat_map(|&byte| packets.push(byte))
+        .collect();
+      inner.extend(packets.end_stream());
+      for packet in &mut inner {
+        packet.stream = message.stream;
+        packet.offset += message.offset;
+      }
+      frames.push(message);
+      frames.extend(inner);
+    }
+    frames
+  }
+}
+
+impl ByteSink for LayeredFramer {
+  type Item = Frame;
+
+  fn push(&mut self, byte: u8) -> Vec<Frame> {
+    let messages = self.messages.push(byte);
+    self.with_packets(messages)
+  }
+
+  fn end_stream(&mut self) -> Vec<Frame> {
+    let messages = self.messages.end_stream();
+    self.with_packets(messages)
+  }
+}
+
+// ---------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn frames(sink: &mut impl ByteSink<Item = Frame>, line: &str) -> Vec<(FrameKind, usize, String)> {
+    let mut frames: Vec<Frame> = line.bytes().flat_map(|byte| sink.push(byte)).collect();
+    frames.extend(sink.end_stream());
+    frames
+      .into_iter()
+      .map(|frame| {
+        (
+          frame.kind,
+          frame.offset,
+          String::from_utf8(frame.data).unwrap(),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn packets_inside_messages() {
+    let mut framer = LayeredFramer::new(PACKET_MARKER_LEN, MESSAGE_MARKER_LEN);
+    assert_eq!(
+      frames(&mut framer, "mjqjpqmgbljsphdztnvjfqwrcgsmlb"),
+      vec![
+        (FrameKind::Message, 19, "jfqwrcgsmlb".to_string()),
+        (FrameKind::Packet, 23, "r".to_string()),
+        (FrameKind::Packet, 28, "lb".to_string()),
+      ]
+    );
+  }
+
+  fn events(input: &str, markers: &[(Marker, usize)], per_line: bool) -> Vec<Event> {
+    let mut decoder = StreamDecoder::new(markers);
+    let mut events = vec![];
+    decode(input.as_bytes(), &mut decoder, per_line, |event| {
+      events.push(event);
+      Ok(())
+    })
+    .unwrap();
+    events
+  }
+
+  #[test]
+  fn every_sample_line() {
+    let markers = [
+      (Marker::StartOfPacket, PACKET_MARKER_LEN),
+      (Marker::StartOfMessage, MESSAGE_MARKER_LEN),
+    ];
+    let events = events(include_str!("../input/test.txt"), &markers, true);
+    let offsets: Vec<(Option<usize>, Option<usize>)> = (0..5)
+      .map(|stream| {
+        let line: Vec<Event> = events
+          .iter()
+          .filter(|e| e.stream == stream)
+          .copied()
+          .collect();
+        (
+          first_offset(&line, Marker::StartOfPacket),
+          first_offset(&line, Marker::StartOfMessage),
+        )
+      })
+      .collect();
+    assert_eq!(
+      offsets,
+      [(5, 23), (7, 19), (6, 23), (10, 29), (11, 26)].map(|(p, m)| (Some(p), Some(m)))
+    );
+  }
+
+  // unlike start-of markers, a custom one is reported wherever it completes.
+  #[test]
+  fn custom_markers_repeat() {
+    let events = events("abcdeffgh", &[(Marker::Custom(4), 4)], false);
+    let offsets: Vec<usize> = events.iter().map(|event| event.offset).collect();
+    assert_eq!(offsets, [4, 5, 6]);
+  }
+
+  #[test]
+  fn no_marker_is_an_error() {
+    let markers = [
+      (Marker::StartOfPacket, PACKET_MARKER_LEN),
+      (Marker::StartOfMessage, MESSAGE_MARKER_LEN),
+    ];
+    let events = events("abcabcabcabc", &markers, false);
+    assert!(events.is_empty());
+    assert!(solve_part1(&events).is_err());
+    assert!(solve_part2(&events).is_err());
+  }
+
+  // back to back markers used to close empty frames.
+  #[test]
+  fn no_empty_frames() {
+    let mut framer = Framer::new(FrameKind::Packet, PACKET_MARKER_LEN);
+    assert_eq!(
+      frames(&mut framer, "abcdefghijk"),
+      vec![
+        (FrameKind::Packet, 4, "e".to_string()),
+        (FrameKind::Packet, 9, "jk".to_string())
+      ]
+    );
+  }
+}
@@ -1,15 +1,18 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::io::{self, Read, Write};
-use std::vec;
+use std::path::{Path, PathBuf};
 use std::{error::Error, result};
 
 type Result<T> = result::Result<T, Box<dyn Error>>;
 
 #[derive(Debug, Clone)]
 enum Command {
-  ChangeDirectory(DirectoryDirection),
-  Listing(Vec<FileType>),
-  FileList,
+  ChangeDirectory(Vec<DirectoryDirection>), // one step per path segment.
+  Listing(Vec<(usize, FileType)>),          // every entry with its own line number.
+  PrintWorkingDirectory(Option<(usize, String)>),
+  Unknown(String),
+  StrayOutput(String), // output after a command that doesn't print anything.
 }
 
 #[derive(Debug, Clone)]
@@ -21,153 +24,434 @@ enum DirectoryDirection {
 
 #[derive(Debug, Clone)]
 enum FileType {
-  File { size: i32, _name: String },
+  File { size: u64, name: String },
   Folder(String),
+  Unreadable(String),
 }
 
 #[derive(Debug)]
 struct File {
-  size: i32,
+  name: String,
+  size: u64,
+  line: usize,
 }
 
-fn parse_cmd(input: &str) -> Command {
-  let cmd = input
-    .lines()
-    .map(|line| {
-      let cmd_list = line.split(' ').collect::<Vec<_>>();
-      match cmd_list[0] {
-        "cd" => match cmd_list[1] {
-          "/" => Some(Command::ChangeDirectory(DirectoryDirection::Root)),
-          ".." => Some(Command::ChangeDirectory(DirectoryDirection::Up)),
-          name => Some(Command::ChangeDirectory(DirectoryDirection::Down(
-            name.to_string(),
-          ))),
-        },
-        "ls" => {
-          let file_list = input.lines().skip(1).map(parse_type).collect::<Vec<_>>();
-          Some(Command::Listing(file_list))
-        }
-        _ => Some(Command::FileList),
+#[derive(Debug)]
+struct Directory {
+  name: String,
+  parent: Option<usize>,
+  children: Vec<usize>,
+  files: Vec<File>,
+  size: u64,                // total size of everything below, filled in by `compute_sizes`.
+  shown_at: Option<usize>,  // line of the `dir` entry that revealed it.
+  listed_at: Option<usize>, // line of the first `ls` inside it.
+}
+
+// directories live in one `Vec` and point at each other by index, the root is always 0.
+// a child is always pushed after its parent, so walking the ids backwards visits
+// every directory before its parent.
+//
+// the transcript is trusted as far as possible, anything odd is kept in `issues`
+// and merged like this:
+// - listing a directory again adds to what is already known, a file listed again
+//   keeps the size from the latest listing.
+// - `cd` into a directory no `ls` has shown creates it.
+// - `cd ..` at the root stays at the root.
+// - a directory that was never listed counts as empty.
+#[derive(Debug)]
+struct FileSystem {
+  dirs: Vec<Directory>,
+  issues: Vec<Issue>,
+}
+
+#[derive(Debug)]
+struct Issue {
+  line: usize,
+  kind: IssueKind,
+}
+
+#[derive(Debug)]
+enum IssueKind {
+  DuplicateListing {
+    path: String,
+    first_line: usize,
+  },
+  ConflictingSize {
+    path: String,
+    size: u64,
+    previous: u64,
+    first_line: usize,
+  },
+  UnknownDirectory {
+    path: String,
+  },
+  AboveRoot,
+  Unexplored {
+    path: String,
+  },
+  UnknownCommand {
+    cmd: String,
+  },
+  StrayOutput {
+    text: String,
+  },
+  UnreadableEntry {
+    text: String,
+  },
+  PwdMismatch {
+    printed: String,
+    path: String,
+  },
+}
+
+// a flattened view of one directory or file, see `FileSystem::walk`.
+#[derive(Debug)]
+struct Entry {
+  name: String,
+  path: String,
+  depth: usize,
+  is_dir: bool,
+  size: u64,
+}
+
+#[derive(Debug)]
+struct Options {
+  tree: bool,
+  du: bool,
+  find_name: Option<String>,
+  min_size: Option<u64>,
+  max_size: Option<u64>,
+  disk_size: u64,
+  required: u64,
+  plan: bool,
+  minimize: Minimize,
+  check: bool,
+  generate: Option<PathBuf>,
+  materialize: Option<PathBuf>,
+  round_trip: bool,
+  json: bool,
+  dot: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Minimize {
+  Bytes,
+  Count,
+}
+
+// what `min_bytes_cleanup` knows about one directory: the totals it can free,
+// and the totals reachable before each of its children was added in.
+#[derive(Debug, Clone, Default)]
+struct Reach {
+  totals: Vec<u64>,
+  before: Vec<Vec<u64>>,
+}
+
+// a path under the system temp dir that's removed when dropped, however the
+// code using it returns.
+#[derive(Debug)]
+struct TempDir(PathBuf);
+
+const ROOT: usize = 0;
+const DISK_SIZE: u64 = 70_000_000;
+const REQUIRED: u64 = 30_000_000;
+
+// a line starting with `$ ` is a command, everything up to the next one is its
+// output. line numbers start at 1 to match an editor.
+fn parse_transcript(input: &str) -> Vec<(usize, Command)> {
+  let mut commands: Vec<(usize, Command)> = vec![];
+
+  for (i, line) in input.lines().enumerate() {
+    let line_no = i + 1;
+    if let Some(cmd) = line.strip_prefix("$ ") {
+      commands.push((line_no, parse_cmd(cmd)));
+      continue;
+    }
+
+    match commands.last_mut() {
+      Some((_, Command::Listing(files))) => files.push((line_no, parse_type(line))),
+      Some((_, Command::PrintWorkingDirectory(printed @ None))) => {
+        *printed = Some((line_no, line.to_string()))
       }
-    })
-    .collect::<Vec<_>>();
+      // whatever an unknown command prints is its own business.
+      Some((_, Command::Unknown(_))) => (),
+      _ => commands.push((line_no, Command::StrayOutput(line.to_string()))),
+    }
+  }
+
+  commands
+}
 
-  cmd[0].clone().unwrap()
+fn parse_cmd(cmd: &str) -> Command {
+  match cmd.split_once(' ').unwrap_or((cmd, "")) {
+    ("cd", path) if !path.is_empty() => Command::ChangeDirectory(parse_path(path)),
+    ("ls", "") => Command::Listing(vec![]),
+    ("pwd", "") => Command::PrintWorkingDirectory(None),
+    _ => Command::Unknown(cmd.to_string()),
+  }
 }
 
+// `cd` takes whole paths, eg. `/a/b`, `a/b/../c` or `./a`.
+fn parse_path(path: &str) -> Vec<DirectoryDirection> {
+  let mut steps = vec![];
+  if path.starts_with('/') {
+    steps.push(DirectoryDirection::Root);
+  }
+  path.split('/').for_each(|segment| match segment {
+    "" | "." => (),
+    ".." => steps.push(DirectoryDirection::Up),
+    name => steps.push(DirectoryDirection::Down(name.to_string())),
+  });
+  steps
+}
+
+// splits only at the first space, names may contain spaces themselves.
 fn parse_type(cmd: &str) -> FileType {
-  let cmd_list = cmd.split(' ').collect::<Vec<_>>();
-  match cmd_list[0] {
-    "dir" => FileType::Folder(cmd_list[1].to_string()),
-    _ => FileType::File {
-      size: cmd_list[0].parse::<i32>().unwrap(),
-      _name: cmd_list[1].to_string(),
+  match cmd.split_once(' ') {
+    Some(("dir", name)) => FileType::Folder(name.to_string()),
+    Some((size, name)) => match size.parse::<u64>() {
+      Ok(size) => FileType::File {
+        size,
+        name: name.to_string(),
+      },
+      Err(_) => FileType::Unreadable(cmd.to_string()),
     },
+    None => FileType::Unreadable(cmd.to_string()),
   }
 }
 
-// Find all of the directories with a total size of at most 100000.
-// What is the sum of the total sizes of those directories?
-fn solve_part1(input: &str) -> Result<()> {
-  let mut filesystem: HashMap<String, Vec<File>> = HashMap::new();
-  let mut context: Vec<String> = vec![];
-  let mut sizes: HashMap<String, i32> = HashMap::new();
-
-  input
-    .split("$ ")
-    .skip(1) // skip first empty string
-    .for_each(|line| match parse_cmd(line) {
-      Command::ChangeDirectory(cmd) => match cmd {
-        DirectoryDirection::Root => context.push("".to_string()),
-        DirectoryDirection::Up => {
-          context.pop();
+impl FileSystem {
+  fn from_commands(commands: &[(usize, Command)]) -> Self {
+    let mut fs = FileSystem {
+      dirs: vec![Directory::new("/", None)],
+      issues: vec![],
+    };
+    let mut cwd = ROOT;
+
+    commands.iter().for_each(|(line, cmd)| match cmd {
+      Command::ChangeDirectory(steps) => steps.iter().for_each(|step| match step {
+        DirectoryDirection::Root => {
+          // nothing lists the root, the first `cd /` is where it shows up.
+          fs.dirs[ROOT].shown_at.get_or_insert(*line);
+          cwd = ROOT;
         }
-        DirectoryDirection::Down(dir) => context.push(dir),
-      },
+        DirectoryDirection::Up => match fs.dirs[cwd].parent {
+          Some(parent) => cwd = parent,
+          None => fs.report(*line, IssueKind::AboveRoot),
+        },
+        DirectoryDirection::Down(name) => {
+          cwd = fs.child_or_insert(cwd, name);
+          if fs.dirs[cwd].shown_at.is_none() {
+            let path = fs.path(cwd);
+            fs.report(*line, IssueKind::UnknownDirectory { path });
+          }
+        }
+      }),
+      Command::PrintWorkingDirectory(Some((line, printed))) => {
+        let path = fs.path(cwd);
+        if *printed != path {
+          let printed = printed.clone();
+          fs.report(*line, IssueKind::PwdMismatch { printed, path });
+        }
+      }
+      Command::PrintWorkingDirectory(None) => (),
+      Command::Unknown(cmd) => {
+        let cmd = cmd.clone();
+        fs.report(*line, IssueKind::UnknownCommand { cmd });
+      }
+      Command::StrayOutput(text) => {
+        let text = text.clone();
+        fs.report(*line, IssueKind::StrayOutput { text });
+      }
       Command::Listing(files) => {
-        filesystem.entry(context.join("/")).or_insert(vec![]);
-
-        files.iter().for_each(|file| match file {
-          FileType::File { size, _name } => {
-            filesystem.entry(context.join("/")).and_modify(|vec| {
-              vec.push(File { size: *size });
-            });
+        match fs.dirs[cwd].listed_at {
+          Some(first_line) => {
+            let path = fs.path(cwd);
+            fs.report(*line, IssueKind::DuplicateListing { path, first_line });
+          }
+          None => fs.dirs[cwd].listed_at = Some(*line),
+        }
+        files.iter().for_each(|(line, file)| match file {
+          FileType::File { size, name } => fs.add_file(cwd, name, *size, *line),
+          FileType::Folder(name) => {
+            let id = fs.child_or_insert(cwd, name);
+            fs.dirs[id].shown_at.get_or_insert(*line);
+          }
+          FileType::Unreadable(text) => {
+            let text = text.clone();
+            fs.report(*line, IssueKind::UnreadableEntry { text });
           }
-          FileType::Folder(_) => (),
         })
       }
-      // skip filelist (after `ls`),
-      // since we already collect the list in `Command::Listing` process.
-      Command::FileList => (),
     });
 
-  filesystem.iter().for_each(|(path, files)| {
-    let dirs = path.split('/').collect::<Vec<&str>>();
-    let size = files.iter().map(|File { size, .. }| size).sum::<i32>();
+    for id in 0..fs.dirs.len() {
+      if let (Some(line), None) = (fs.dirs[id].shown_at, fs.dirs[id].listed_at) {
+        let path = fs.path(id);
+        fs.report(line, IssueKind::Unexplored { path });
+      }
+    }
+    fs.issues.sort_by_key(|issue| issue.line);
 
-    (0..dirs.len()).for_each(|i| {
-      sizes
-        .entry(dirs[0..=i].join("/"))
-        .and_modify(|v| *v += size)
-        .or_insert(size);
-    })
-  });
+    fs.compute_sizes();
+    fs
+  }
 
-  let res = sizes
-    .iter()
-    .filter_map(|(_, &size)| if size < 100000 { Some(size) } else { None })
-    .sum::<i32>();
+  fn report(&mut self, line: usize, kind: IssueKind) {
+    self.issues.push(Issue { line, kind });
+  }
 
-  writeln!(io::stdout(), "{:?}", res)?;
-  Ok(())
-}
+  fn child(&self, dir: usize, name: &str) -> Option<usize> {
+    self.dirs[dir]
+      .children
+      .iter()
+      .copied()
+      .find(|&id| self.dirs[id].name == name)
+  }
 
-// Find the smallest directory that, if deleted,
-// would free up enough space on the filesystem to run the update.
-// What is the total size of that directory?
-fn solve_part2(input: &str) -> Result<()> {
-  let mut filesystem: HashMap<String, Vec<File>> = HashMap::new();
-  let mut context: Vec<String> = vec![];
-  let mut sizes: HashMap<String, i32> = HashMap::new();
-
-  input
-    .split("$ ")
-    .skip(1) // skip first empty string
-    .for_each(|line| match parse_cmd(line) {
-      Command::ChangeDirectory(cmd) => match cmd {
-        DirectoryDirection::Root => context.push("".to_string()),
-        DirectoryDirection::Up => {
-          context.pop();
-        }
-        DirectoryDirection::Down(dir) => context.push(dir),
-      },
-      Command::Listing(files) => {
-        filesystem.entry(context.join("/")).or_insert(vec![]);
+  fn child_or_insert(&mut self, dir: usize, name: &str) -> usize {
+    if let Some(id) = self.child(dir, name) {
+      return id;
+    }
+    let id = self.dirs.len();
+    self.dirs.push(Directory::new(name, Some(dir)));
+    self.dirs[dir].children.push(id);
+    id
+  }
 
-        files.iter().for_each(|file| match file {
-          FileType::File { size, _name } => {
-            filesystem.entry(context.join("/")).and_modify(|vec| {
-              vec.push(File { size: *size });
-            });
-          }
-          FileType::Folder(_) => (),
-        })
+  // a directory can't hold two files with the same name, listing it again replaces it.
+  fn add_file(&mut self, dir: usize, name: &str, size: u64, line: usize) {
+    let files = &mut self.dirs[dir].files;
+    match files.iter_mut().find(|file| file.name == name) {
+      Some(file) if file.size != size => {
+        let (previous, first_line) = (file.size, file.line);
+        file.size = size;
+        let path = format!("{}/{}", self.path(dir).trim_end_matches('/'), name);
+        self.report(
+          line,
+          IssueKind::ConflictingSize {
+            path,
+            size,
+            previous,
+            first_line,
+          },
+        );
       }
-      Command::FileList => (),
+      Some(_) => (),
+      None => files.push(File {
+        name: name.to_string(),
+        size,
+        line,
+      }),
+    }
+  }
+
+  // one bottom-up pass, children are always visited before their parents.
+  fn compute_sizes(&mut self) {
+    for id in (0..self.dirs.len()).rev() {
+      let dir = &self.dirs[id];
+      let own = dir.files.iter().map(|file| file.size).sum::<u64>();
+      let nested = dir.children.iter().map(|&c| self.dirs[c].size).sum::<u64>();
+      self.dirs[id].size = own + nested;
+    }
+  }
+
+  fn size(&self, dir: usize) -> u64 {
+    self.dirs[dir].size
+  }
+
+  fn used(&self) -> u64 {
+    self.size(ROOT)
+  }
+
+  fn path(&self, dir: usize) -> String {
+    let mut names = vec![];
+    let mut curr = dir;
+    while let Some(parent) = self.dirs[curr].parent {
+      names.push(self.dirs[curr].name.as_str());
+      curr = parent;
+    }
+    names.reverse();
+    format!("/{}", names.join("/"))
+  }
+
+  // every directory and file, depth-first and sorted by name within a directory
+  // (the same order as the listing in the puzzle text).
+  fn walk(&self) -> Vec<Entry> {
+    let mut entries = vec![];
+    self.walk_dir(ROOT, 0, &mut entries);
+    entries
+  }
+
+  fn walk_dir(&self, dir: usize, depth: usize, entries: &mut Vec<Entry>) {
+    let path = self.path(dir);
+    entries.push(Entry {
+      name: self.dirs[dir].name.clone(),
+      path: path.clone(),
+      depth,
+      is_dir: true,
+      size: self.size(dir),
     });
 
-  filesystem.iter().for_each(|(path, files)| {
-    let dirs = path.split('/').collect::<Vec<&str>>();
-    let size = files.iter().map(|File { size, .. }| size).sum::<i32>();
+    let mut children: Vec<(&str, Option<usize>, u64)> = self.dirs[dir]
+      .children
+      .iter()
+      .map(|&id| (self.dirs[id].name.as_str(), Some(id), self.size(id)))
+      .chain(
+        self.dirs[dir]
+          .files
+          .iter()
+          .map(|file| (file.name.as_str(), None, file.size)),
+      )
+      .collect();
+    children.sort_by_key(|(name, _, _)| *name);
 
-    (0..dirs.len()).for_each(|i| {
-      sizes
-        .entry(dirs[0..=i].join("/"))
-        .and_modify(|v| *v += size)
-        .or_insert(size);
-    })
-  });
+    for (name, id, size) in children {
+      match id {
+        Some(id) => self.walk_dir(id, depth + 1, entries),
+        None => entries.push(Entry {
+          name: name.to_string(),
+          path: format!("{}/{}", path.trim_end_matches('/'), name),
+          depth: depth + 1,
+          is_dir: false,
+          size,
+        }),
+      }
+    }
+  }
+
+  fn dir_sizes(&self) -> impl Iterator<Item = u64> + '_ {
+    self.dirs.iter().map(|dir| dir.size)
+  }
+}
+
+impl Directory {
+  fn new(name: &str, parent: Option<usize>) -> Self {
+    Directory {
+      name: name.to_string(),
+      parent,
+      children: vec![],
+      files: vec![],
+      size: 0,
+      shown_at: None,
+      listed_at: None,
+    }
+  }
+}
 
+// Find all of the directories with a total size of at most 100000.
+// What is the sum of the total sizes of those directories?
+fn solve_part1(fs: &FileSystem) -> Result<()> {
+  let res = fs.dir_sizes().filter(|&size| size <= 100000).sum::<u64>();
+
+  writeln!(io::stdout(), "{:?}", res)?;
+  Ok(())
+}
+
+// Find the smallest directory that, if deleted,
+// would free up enough space on the filesystem to run the update.
+// What is the total size of that directory?
+fn solve_part2(fs: &FileSystem, opts: &Options) -> Result<()> {
   // total: 70,000,000
   // require: 30,000,000
 
@@ -175,36 +459,767 @@ fn solve_part2(input: &str) -> Result<()> {
   // current_free_space: 29,731,435 (70,000,000 - 40,268,565)
   // space_to_freeup: 268,565 (30,000,000 - 29,731,435)
 
-  let total = 70_000_000;
-  let require = 30_000_000;
-
-  let current_used = sizes.get("").unwrap();
-  let current_free_space = total - current_used;
-  let space_to_freeup = require - current_free_space;
+  let space_to_freeup = space_to_freeup(fs, opts);
 
   // find dir size that, at least when being freed up
   // there will be enough space for updating.
-  let s = sizes
+  let res = fs
+    .dir_sizes()
+    .filter(|&size| size >= space_to_freeup)
+    .min()
+    .ok_or("no directory is big enough")?;
+
+  writeln!(io::stdout(), "{:?}", res)?;
+  Ok(())
+}
+
+fn space_to_freeup(fs: &FileSystem, opts: &Options) -> u64 {
+  let current_free_space = opts.disk_size.saturating_sub(fs.used());
+  opts.required.saturating_sub(current_free_space)
+}
+
+// finds the set of directories to delete that frees at least `space_to_freeup`,
+// deleting the fewest bytes (or the fewest directories) possible.
+// a directory is deleted together with everything in it, so a chosen directory
+// never has a chosen ancestor or descendant, that would count its bytes twice.
+fn plan_cleanup(fs: &FileSystem, opts: &Options) -> Result<()> {
+  let mut out = io::stdout();
+  let need = space_to_freeup(fs, opts);
+
+  writeln!(
+    out,
+    "disk {}, used {}, free {}, required {}",
+    opts.disk_size,
+    fs.used(),
+    opts.disk_size.saturating_sub(fs.used()),
+    opts.required
+  )?;
+  if need == 0 {
+    writeln!(out, "already enough free space, nothing to delete")?;
+    return Ok(());
+  }
+  if opts.disk_size < opts.required {
+    return Err("the update is bigger than the whole disk".into());
+  }
+
+  // the root is always big enough on its own, so one directory always does it.
+  let single = fs
+    .dirs
     .iter()
-    .filter_map(|(_, &size)| {
-      if size > space_to_freeup {
-        Some(size)
-      } else {
-        None
+    .enumerate()
+    .filter(|(_, dir)| dir.size >= need)
+    .min_by_key(|(_, dir)| dir.size)
+    .map(|(id, _)| id)
+    .ok_or("no directory frees enough space")?;
+
+  let (dirs, reason) = match opts.minimize {
+    Minimize::Count => (
+      vec![single],
+      "the smallest directory that frees enough on its own",
+    ),
+    Minimize::Bytes => (
+      min_bytes_cleanup(fs, need, fs.size(single)).ok_or("no directories free enough space")?,
+      "the fewest bytes any set of separate directories can free",
+    ),
+  };
+  let bytes = dirs.iter().map(|&id| fs.size(id)).sum::<u64>();
+
+  writeln!(
+    out,
+    "need to free {}, deleting {} directories frees {} ({} more than needed)",
+    need,
+    dirs.len(),
+    bytes,
+    bytes - need
+  )?;
+  writeln!(out, "picked {}:", reason)?;
+  let mut dirs = dirs;
+  dirs.sort_by_key(|&id| fs.path(id));
+  for id in dirs {
+    writeln!(out, "  {}\t{}", fs.size(id), fs.path(id))?;
+  }
+  if opts.minimize == Minimize::Bytes {
+    writeln!(
+      out,
+      "the smallest single directory would free {}",
+      fs.size(single)
+    )?;
+  }
+  Ok(())
+}
+
+// subset sum over the tree, bottom up: a directory can free either its whole
+// size or any mix of what its children can free on their own. every set of
+// totals is a sorted list, and only totals that could still be the answer are
+// kept: anything short of `need`, plus the smallest one that's enough as long
+// as it's no more than `limit` (what the best single directory frees).
+fn min_bytes_cleanup(fs: &FileSystem, need: u64, limit: u64) -> Option<Vec<usize>> {
+  let mut reach = vec![Reach::default(); fs.dirs.len()];
+  cleanup_totals(fs, ROOT, need, limit, &mut reach);
+  let &bytes = reach[ROOT].totals.iter().find(|&&bytes| bytes >= need)?;
+
+  let mut dirs = vec![];
+  pick_cleanup(fs, ROOT, bytes, &reach, &mut dirs);
+  Some(dirs)
+}
+
+fn cleanup_totals(fs: &FileSystem, dir: usize, need: u64, limit: u64, reach: &mut [Reach]) {
+  let mut totals = vec![0];
+  for &child in &fs.dirs[dir].children {
+    cleanup_totals(fs, child, need, limit, reach);
+    let mut next = vec![];
+    for &share in &reach[child].totals {
+      let shifted = totals.iter().map(|&sum| sum + share);
+      next = prune_totals(merge_sorted(&next, shifted), need, limit);
+    }
+    reach[dir].before.push(std::mem::replace(&mut totals, next));
+  }
+  totals.push(fs.size(dir));
+  totals.sort_unstable();
+  totals.dedup();
+  reach[dir].totals = prune_totals(totals, need, limit);
+}
+
+fn prune_totals(mut totals: Vec<u64>, need: u64, limit: u64) -> Vec<u64> {
+  let enough = totals.partition_point(|&bytes| bytes < need);
+  totals.truncate(enough + 1);
+  totals.retain(|&bytes| bytes <= limit);
+  totals
+}
+
+// the union of two sorted lists, still sorted and without repeats.
+fn merge_sorted(a: &[u64], b: impl Iterator<Item = u64>) -> Vec<u64> {
+  let mut merged = Vec::with_capacity(a.len());
+  let mut a = a.iter().copied().peekable();
+  let mut b = b.peekable();
+  while let Some(next) = match (a.peek(), b.peek()) {
+    (Some(&x), Some(&y)) if x <= y => a.next(),
+    (Some(_), Some(_)) | (None, Some(_)) => b.next(),
+    (Some(_), None) => a.next(),
+    (None, None) => None,
+  } {
+    if merged.last() != Some(&next) {
+      merged.push(next);
+    }
+  }
+  merged
+}
+
+// walks the choices for freeing exactly `bytes` in `dir` back down the tree:
+// the whole directory if that's its size, otherwise the last child takes some
+// share that leaves a total reachable by the ones before it.
+fn pick_cleanup(
+  fs: &FileSystem,
+  dir: usize,
+  mut bytes: u64,
+  reach: &[Reach],
+  dirs: &mut Vec<usize>,
+) {
+  if bytes == 0 {
+    return;
+  }
+  if bytes == fs.size(dir) {
+    dirs.push(dir);
+    return;
+  }
+  let children = &fs.dirs[dir].children;
+  for (k, &child) in children.iter().enumerate().rev() {
+    let before = &reach[dir].before[k];
+    let &share = reach[child]
+      .totals
+      .iter()
+      .find(|&&share| share <= bytes && before.binary_search(&(bytes - share)).is_ok())
+      .unwrap();
+    pick_cleanup(fs, child, share, reach, dirs);
+    bytes -= share;
+  }
+}
+
+impl fmt::Display for Issue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: ", self.line)?;
+    match &self.kind {
+      IssueKind::DuplicateListing { path, first_line } => {
+        write!(
+          f,
+          "{} listed again (first at line {}), entries merged",
+          path, first_line
+        )
+      }
+      IssueKind::ConflictingSize {
+        path,
+        size,
+        previous,
+        first_line,
+      } => write!(
+        f,
+        "{} is {} but was {} at line {}, keeping {}",
+        path, size, previous, first_line, size
+      ),
+      IssueKind::UnknownDirectory { path } => {
+        write!(f, "cd into {} which no ls has shown", path)
+      }
+      IssueKind::AboveRoot => write!(f, "cd .. at the root, staying at /"),
+      IssueKind::Unexplored { path } => {
+        write!(f, "{} is never listed, counted as empty", path)
+      }
+      IssueKind::UnknownCommand { cmd } => write!(f, "unknown command `{}`, ignored", cmd),
+      IssueKind::StrayOutput { text } => {
+        write!(
+          f,
+          "output `{}` doesn't belong to any command, ignored",
+          text
+        )
+      }
+      IssueKind::UnreadableEntry { text } => {
+        write!(f, "can't read ls entry `{}`, ignored", text)
+      }
+      IssueKind::PwdMismatch { printed, path } => {
+        write!(
+          f,
+          "pwd printed {} but the current directory is {}",
+          printed, path
+        )
       }
-    })
-    .collect::<Vec<i32>>();
+    }
+  }
+}
 
-  let res = s.iter().min().unwrap(); // get the smallest size of dir-size that match space_to_freeup.
-  writeln!(io::stdout(), "{:?}", res)?;
+fn print_issues(fs: &FileSystem) -> Result<()> {
+  let mut out = io::stdout();
+  for issue in &fs.issues {
+    writeln!(out, "{}", issue)?;
+  }
+  writeln!(out, "{} issues found", fs.issues.len())?;
+  Ok(())
+}
+
+// - / (dir, size=48381165)
+//   - a (dir, size=94853)
+//     - e (dir, size=584)
+//       - i (file, size=584)
+fn print_tree(fs: &FileSystem) -> Result<()> {
+  let mut out = io::stdout();
+  for entry in fs.walk() {
+    let kind = if entry.is_dir { "dir" } else { "file" };
+    writeln!(
+      out,
+      "{}- {} ({}, size={})",
+      "  ".repeat(entry.depth),
+      entry.name,
+      kind,
+      entry.size
+    )?;
+  }
+  Ok(())
+}
+
+// like `du | sort -rn`, every directory with its recursive size, biggest first.
+fn print_du(fs: &FileSystem) -> Result<()> {
+  let mut out = io::stdout();
+  let mut dirs = fs
+    .walk()
+    .into_iter()
+    .filter(|entry| entry.is_dir)
+    .collect::<Vec<Entry>>();
+  dirs.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+
+  for entry in dirs {
+    writeln!(out, "{}\t{}", entry.size, entry.path)?;
+  }
+  Ok(())
+}
+
+// files and directories matching every given filter, in tree order.
+fn print_find(fs: &FileSystem, opts: &Options) -> Result<()> {
+  let mut out = io::stdout();
+  let found = fs.walk().into_iter().filter(|entry| {
+    opts
+      .find_name
+      .as_ref()
+      .is_none_or(|name| &entry.name == name)
+      && opts.min_size.is_none_or(|min| entry.size >= min)
+      && opts.max_size.is_none_or(|max| entry.size <= max)
+  });
+
+  for entry in found {
+    let suffix = if entry.is_dir && entry.depth > 0 {
+      "/"
+    } else {
+      ""
+    };
+    writeln!(out, "{}\t{}{}", entry.size, entry.path, suffix)?;
+  }
+  Ok(())
+}
+
+// walks a real directory and prints the `$ cd` / `$ ls` session that explores it,
+// in the same shape as the puzzle input. anything that isn't a plain file or
+// directory (eg. symlinks) is left out.
+fn generate_transcript(root: &Path) -> Result<String> {
+  let mut lines = vec!["$ cd /".to_string()];
+  generate_dir(root, &mut lines)?;
+  Ok(lines.join("\n"))
+}
+
+fn generate_dir(dir: &Path, lines: &mut Vec<String>) -> Result<()> {
+  let mut dirs = vec![];
+  let mut files = vec![];
+  for entry in std::fs::read_dir(dir)? {
+    let entry = entry?;
+    let file_type = entry.file_type()?;
+    let name = entry
+      .file_name()
+      .into_string()
+      .map_err(|name| format!("not a utf-8 name: {:?}", name))?;
+    if file_type.is_dir() {
+      dirs.push(name);
+    } else if file_type.is_file() {
+      let size = entry.metadata()?.len();
+      files.push((name, size));
+    }
+  }
+  dirs.sort();
+  files.sort();
+
+  lines.push("$ ls".to_string());
+  dirs
+    .iter()
+    .for_each(|name| lines.push(format!("dir {}", name)));
+  files
+    .iter()
+    .for_each(|(name, size)| lines.push(format!("{} {}", size, name)));
+
+  for name in dirs {
+    lines.push(format!("$ cd {}", name));
+    generate_dir(&dir.join(&name), lines)?;
+    lines.push("$ cd ..".to_string());
+  }
+  Ok(())
+}
+
+// recreates the tree under `target` (which must not exist yet), every file
+// becomes a sparse file of the listed size so nothing is actually written.
+// if anything fails, whatever was created so far is removed again.
+fn materialize(fs: &FileSystem, target: &Path) -> Result<()> {
+  std::fs::create_dir(target)?;
+  let res = materialize_dir(fs, ROOT, target);
+  if res.is_err() {
+    let _ = std::fs::remove_dir_all(target);
+  }
+  res
+}
+
+fn materialize_dir(fs: &FileSystem, dir: usize, path: &Path) -> Result<()> {
+  for file in &fs.dirs[dir].files {
+    check_name(&file.name)?;
+    std::fs::File::create(path.join(&file.name))?.set_len(file.size)?;
+  }
+  for &child in &fs.dirs[dir].children {
+    let name = &fs.dirs[child].name;
+    check_name(name)?;
+    std::fs::create_dir(path.join(name))?;
+    materialize_dir(fs, child, &path.join(name))?;
+  }
+  Ok(())
+}
+
+// names come straight from the transcript, they must not point outside of their directory.
+fn check_name(name: &str) -> Result<()> {
+  if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+    return Err(format!("refusing to create {:?}", name).into());
+  }
+  Ok(())
+}
+
+// materializes the transcript in a temp directory, then checks that both a
+// transcript generated back from it and `du --apparent-size` agree with the sizes
+// computed here.
+fn round_trip(fs: &FileSystem) -> Result<()> {
+  let target = TempDir::new("round-trip");
+  materialize(fs, target.path())?;
+  compare_round_trip(fs, target.path())
+}
+
+impl TempDir {
+  // not created yet, `materialize` does that.
+  fn new(name: &str) -> Self {
+    TempDir(std::env::temp_dir().join(format!("day07-{}-{}", name, std::process::id())))
+  }
+
+  fn path(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_dir_all(&self.0);
+  }
+}
+
+fn compare_round_trip(fs: &FileSystem, target: &Path) -> Result<()> {
+  let mut mismatches = vec![];
+
+  let regenerated = FileSystem::from_commands(&parse_transcript(&generate_transcript(target)?));
+  let expected = fs
+    .walk()
+    .into_iter()
+    .map(|entry| (entry.path, entry.size))
+    .collect::<Vec<(String, u64)>>();
+  let actual = regenerated
+    .walk()
+    .into_iter()
+    .map(|entry| (entry.path, entry.size))
+    .collect::<Vec<(String, u64)>>();
+  if expected != actual {
+    mismatches.push("regenerated transcript differs from the original".to_string());
+  }
+
+  let output = std::process::Command::new("du")
+    .args(["--apparent-size", "--block-size=1"])
+    .arg(target)
+    .output()?;
+  if !output.status.success() {
+    return Err(String::from_utf8_lossy(&output.stderr).to_string().into());
+  }
+  let du = String::from_utf8(output.stdout)?
+    .lines()
+    .filter_map(|line| line.split_once('\t'))
+    .map(|(size, path)| Ok((PathBuf::from(path), size.parse::<u64>()?)))
+    .collect::<Result<HashMap<PathBuf, u64>>>()?;
+
+  for (id, dir) in fs.dirs.iter().enumerate() {
+    let path = target.join(fs.path(id).trim_start_matches('/'));
+    let reported = du
+      .get(&path)
+      .ok_or_else(|| format!("du did not report {}", path.display()))?;
+    // du also counts the size of the directory entries themselves.
+    let own = dir_overhead(&path)?;
+    if reported - own != dir.size {
+      mismatches.push(format!(
+        "{}: du says {}, expected {}",
+        fs.path(id),
+        reported - own,
+        dir.size
+      ));
+    }
+  }
+
+  if !mismatches.is_empty() {
+    return Err(mismatches.join("\n").into());
+  }
+  writeln!(
+    io::stdout(),
+    "round trip ok, {} directories match du --apparent-size",
+    fs.dirs.len()
+  )?;
   Ok(())
 }
 
+fn dir_overhead(dir: &Path) -> Result<u64> {
+  let mut total = std::fs::metadata(dir)?.len();
+  for entry in std::fs::read_dir(dir)? {
+    let entry = entry?;
+    if entry.file_type()?.is_dir() {
+      total += dir_overhead(&entry.path())?;
+    }
+  }
+  Ok(total)
+}
+
+// nested json, every directory with its cumulative size and its children, eg.
+// {"name":"/","type":"dir","size":48381165,"children":[{"name":"b.txt","type":"file","size":14848514}]}
+fn export_json(fs: &FileSystem) -> String {
+  let mut json = String::new();
+  json_dir(fs, ROOT, &mut json);
+  json
+}
+
+fn json_dir(fs: &FileSystem, dir: usize, json: &mut String) {
+  json.push_str(&format!(
+    "{{\"name\":{},\"type\":\"dir\",\"size\":{},\"children\":[",
+    json_string(&fs.dirs[dir].name),
+    fs.size(dir)
+  ));
+  for (i, &child) in fs.dirs[dir].children.iter().enumerate() {
+    if i > 0 {
+      json.push(',');
+    }
+    json_dir(fs, child, json);
+  }
+  for (i, file) in fs.dirs[dir].files.iter().enumerate() {
+    if i > 0 || !fs.dirs[dir].children.is_empty() {
+      json.push(',');
+    }
+    json.push_str(&format!(
+      "{{\"name\":{},\"type\":\"file\",\"size\":{}}}",
+      json_string(&file.name),
+      file.size
+    ));
+  }
+  json.push_str("]}");
+}
+
+fn json_string(text: &str) -> String {
+  let mut escaped = String::from("\"");
+  for c in text.chars() {
+    match c {
+      '"' => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+      c => escaped.push(c),
+    }
+  }
+  escaped.push('"');
+  escaped
+}
+
+// directories only, in the same layout as the `graph.dot` from day12. the fill
+// goes from green to red with the share of the disk a directory takes up.
+fn export_dot(fs: &FileSystem, disk_size: u64) -> String {
+  let mut lines = vec!["digraph {".to_string()];
+  for (id, dir) in fs.dirs.iter().enumerate() {
+    let share = (dir.size as f64 / disk_size.max(1) as f64).min(1.0);
+    lines.push(format!(
+      "    {} [ label = \"{}\\n{}\" style = filled fillcolor = \"{:.3} 0.600 1.000\" ]",
+      id,
+      dir.name.replace('\\', "\\\\").replace('"', "\\\""),
+      dir.size,
+      (1.0 - share) / 3.0
+    ));
+  }
+  for (id, dir) in fs.dirs.iter().enumerate() {
+    for child in &dir.children {
+      lines.push(format!("    {} -> {} [ ]", id, child));
+    }
+  }
+  lines.push("}".to_string());
+  lines.join("\n")
+}
+
+// usage: day07 [--tree] [--du] [--name <name>] [--min-size <n>] [--max-size <n>]
+//              [--disk-size <n>] [--required <n>] [--plan [--minimize bytes|count]]
+//              [--check] [--generate <dir>] [--materialize <dir>] [--round-trip]
+//              [--json] [--dot]
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options> {
+  let mut opts = Options {
+    tree: false,
+    du: false,
+    find_name: None,
+    min_size: None,
+    max_size: None,
+    disk_size: DISK_SIZE,
+    required: REQUIRED,
+    plan: false,
+    minimize: Minimize::Bytes,
+    check: false,
+    generate: None,
+    materialize: None,
+    round_trip: false,
+    json: false,
+    dot: false,
+  };
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--tree" => opts.tree = true,
+      "--du" => opts.du = true,
+      "--name" => opts.find_name = Some(next_value(&mut args, &arg)?),
+      "--min-size" => opts.min_size = Some(next_value(&mut args, &arg)?.parse()?),
+      "--max-size" => opts.max_size = Some(next_value(&mut args, &arg)?.parse()?),
+      "--disk-size" => opts.disk_size = next_value(&mut args, &arg)?.parse()?,
+      "--required" => opts.required = next_value(&mut args, &arg)?.parse()?,
+      "--plan" => opts.plan = true,
+      "--check" => opts.check = true,
+      "--generate" => opts.generate = Some(next_value(&mut args, &arg)?.into()),
+      "--materialize" => opts.materialize = Some(next_value(&mut args, &arg)?.into()),
+      "--round-trip" => opts.round_trip = true,
+      "--json" => opts.json = true,
+      "--dot" => opts.dot = true,
+      "--minimize" => {
+        opts.minimize = match next_value(&mut args, &arg)?.as_str() {
+          "bytes" => Minimize::Bytes,
+          "count" => Minimize::Count,
+          other => return Err(format!("unknown objective: {}", other).into()),
+        }
+      }
+      _ => return Err(format!("unknown option: {}", arg).into()),
+    }
+  }
+  Ok(opts)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+  args
+    .next()
+    .ok_or_else(|| format!("missing value for {}", flag).into())
+}
+
 fn main() -> Result<()> {
+  let opts = parse_options(std::env::args().skip(1))?;
+
+  if let Some(dir) = &opts.generate {
+    writeln!(io::stdout(), "{}", generate_transcript(dir)?)?;
+    return Ok(());
+  }
+
   let mut input = String::new();
   io::stdin().read_to_string(&mut input)?;
 
-  solve_part1(&input)?;
-  solve_part2(&input)?;
+  let fs = FileSystem::from_commands(&parse_transcript(&input));
+
+  if opts.check {
+    return print_issues(&fs);
+  }
+  // only `--check` stops at them, anything else still goes on with a warning.
+  for issue in &fs.issues {
+    writeln!(io::stderr(), "warning: {}", issue)?;
+  }
+  if let Some(dir) = &opts.materialize {
+    return materialize(&fs, dir);
+  }
+  if opts.round_trip {
+    return round_trip(&fs);
+  }
+  if opts.json {
+    writeln!(io::stdout(), "{}", export_json(&fs))?;
+    return Ok(());
+  }
+  if opts.dot {
+    writeln!(io::stdout(), "{}", export_dot(&fs, opts.disk_size))?;
+    return Ok(());
+  }
+  if opts.tree {
+    return print_tree(&fs);
+  }
+  if opts.du {
+    return print_du(&fs);
+  }
+  if opts.find_name.is_some() || opts.min_size.is_some() || opts.max_size.is_some() {
+    return print_find(&fs, &opts);
+  }
+  if opts.plan {
+    return plan_cleanup(&fs, &opts);
+  }
+
+  solve_part1(&fs)?;
+  solve_part2(&fs, &opts)?;
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unlisted_root_is_reported_at_cd() {
+    let fs = FileSystem::from_commands(&parse_transcript("$ pwd\n/\n$ cd /\n$ cd /\n"));
+    assert!(matches!(
+      &fs.issues[..],
+      [Issue { line: 3, kind: IssueKind::Unexplored { path } }] if path == "/"
+    ));
+  }
+
+  #[test]
+  fn unknown_commands_are_reported() {
+    let fs = FileSystem::from_commands(&parse_transcript(
+      "$ cd /\n$ ls\n14848514 b.txt\n$ rm b.txt\n",
+    ));
+    assert!(matches!(
+      &fs.issues[..],
+      [Issue { line: 4, kind: IssueKind::UnknownCommand { cmd } }] if cmd == "rm b.txt"
+    ));
+  }
+
+  // xorshift, good enough for making up trees.
+  struct Rng(u64);
+
+  impl Rng {
+    fn below(&mut self, n: u64) -> u64 {
+      self.0 ^= self.0 << 13;
+      self.0 ^= self.0 >> 7;
+      self.0 ^= self.0 << 17;
+      self.0 % n
+    }
+  }
+
+  fn random_fs(rng: &mut Rng) -> FileSystem {
+    let mut fs = FileSystem::from_commands(&[]);
+    for i in 1..1 + rng.below(10) as usize {
+      let parent = rng.below(i as u64) as usize;
+      fs.child_or_insert(parent, &format!("d{}", i));
+    }
+    for dir in 0..fs.dirs.len() {
+      for f in 0..rng.below(3) {
+        fs.add_file(dir, &format!("f{}", f), 1 + rng.below(50), 0);
+      }
+    }
+    fs.compute_sizes();
+    fs
+  }
+
+  fn is_ancestor(fs: &FileSystem, dir: usize, mut of: usize) -> bool {
+    while let Some(parent) = fs.dirs[of].parent {
+      if parent == dir {
+        return true;
+      }
+      of = parent;
+    }
+    false
+  }
+
+  // every set of directories where none is inside another.
+  fn brute_force_cleanup(fs: &FileSystem, need: u64) -> Option<u64> {
+    let n = fs.dirs.len();
+    (0..1u32 << n)
+      .filter(|set| {
+        let chosen = (0..n).filter(|&i| set & 1 << i != 0).collect::<Vec<_>>();
+        chosen
+          .iter()
+          .all(|&a| chosen.iter().all(|&b| !is_ancestor(fs, a, b)))
+      })
+      .map(|set| {
+        (0..n)
+          .filter(|&i| set & 1 << i != 0)
+          .map(|i| fs.size(i))
+          .sum()
+      })
+      .filter(|&bytes| bytes >= need)
+      .min()
+  }
+
+  #[test]
+  fn cleanup_matches_brute_force() {
+    let mut rng = Rng(42 | 1);
+    for _ in 0..500 {
+      let fs = random_fs(&mut rng);
+      let need = 1 + rng.below(fs.used() + 1);
+      let limit = fs.dir_sizes().filter(|&size| size >= need).min();
+      let expected = brute_force_cleanup(&fs, need);
+      let Some(limit) = limit else {
+        assert_eq!(expected, None);
+        continue;
+      };
+
+      let dirs = min_bytes_cleanup(&fs, need, limit).unwrap();
+      let bytes = dirs.iter().map(|&id| fs.size(id)).sum::<u64>();
+      assert_eq!(Some(bytes), expected);
+      let mut distinct = dirs.clone();
+      distinct.sort_unstable();
+      distinct.dedup();
+      assert_eq!(distinct.len(), dirs.len());
+      for &a in &dirs {
+        assert!(dirs.iter().all(|&b| !is_ancestor(&fs, a, b)));
+      }
+    }
+  }
+
+  // needs a `du` that knows `--apparent-size`, like GNU's.
+  #[test]
+  fn sample_round_trip() {
+    let fs = FileSystem::from_commands(&parse_transcript(include_str!("../input/test.txt")));
+    let target = TempDir::new("test");
+    materialize(&fs, target.path()).unwrap();
+    compare_round_trip(&fs, target.path()).unwrap();
+  }
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+
+type Result<T> = ::std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  Up,
+  Down,
+  Left,
+  Right,
+  UpLeft,
+  UpRight,
+  DownLeft,
+  DownRight,
+}
+
+pub const DIRECTIONS: [Direction; 4] = [
+  Direction::Up,
+  Direction::Down,
+  Direction::Left,
+  Direction::Right,
+];
+
+// the cardinal directions followed by the diagonals.
+pub const ALL_DIRECTIONS: [Direction; 8] = [
+  Direction::Up,
+  Direction::Down,
+  Direction::Left,
+  Direction::Right,
+  Direction::UpLeft,
+  Direction::UpRight,
+  Direction::DownLeft,
+  Direction::DownRight,
+];
+
+// a rectangular grid stored row by row, `(x, y)` is (column, row) with `(0, 0)` at the top left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+  width: usize,
+  height: usize,
+  cells: Vec<T>,
+}
+
+impl Direction {
+  pub fn delta(&self) -> (isize, isize) {
+    match self {
+      Direction::Up => (0, -1),
+      Direction::Down => (0, 1),
+      Direction::Left => (-1, 0),
+      Direction::Right => (1, 0),
+      Direction::UpLeft => (-1, -1),
+      Direction::UpRight => (1, -1),
+      Direction::DownLeft => (-1, 1),
+      Direction::DownRight => (1, 1),
+    }
+  }
+}
+
+impl<T> Grid<T> {
+  pub fn new(width: usize, height: usize, cells: Vec<T>) -> Result<Self> {
+    if cells.len() != width * height {
+      return Err(
+        format!(
+          "{} cells don't make a {}x{} grid",
+          cells.len(),
+          width,
+          height
+        )
+        .into(),
+      );
+    }
+    Ok(Grid {
+      width,
+      height,
+      cells,
+    })
+  }
+
+  // every row has to be as long as the first one.
+  pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Self> {
+    let height = rows.len();
+    let width = rows.first().map_or(0, |row| row.len());
+    if let Some(y) = rows.iter().position(|row| row.len() != width) {
+      return Err(format!("row {} has {} cells, expected {}", y, rows[y].len(), width).into());
+    }
+    Grid::new(width, height, rows.into_iter().flatten().collect())
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  // all cells, row by row.
+  pub fn cells(&self) -> &[T] {
+    &self.cells
+  }
+
+  pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+    if x < self.width && y < self.height {
+      self.cells.get(y * self.width + x)
+    } else {
+      None
+    }
+  }
+
+  #[allow(dead_code)]
+  pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+    if x < self.width && y < self.height {
+      self.cells.get_mut(y * self.width + x)
+    } else {
+      None
+    }
+  }
+
+  pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+    Grid {
+      width: self.width,
+      height: self.height,
+      cells: self.cells.iter().map(f).collect(),
+    }
+  }
+
+  // one step from `(x, y)` towards `dir`, if that's still inside the grid.
+  pub fn step(&self, x: usize, y: usize, dir: Direction) -> Option<(usize, usize)> {
+    let (dx, dy) = dir.delta();
+    let nx = x.checked_add_signed(dx)?;
+    let ny = y.checked_add_signed(dy)?;
+    (nx < self.width && ny < self.height).then_some((nx, ny))
+  }
+
+  // every `(x, y)`, row by row.
+  pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+    let width = self.width;
+    (0..self.height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+  }
+
+  pub fn row(&self, y: usize) -> impl Iterator<Item = &T> {
+    self.cells[y * self.width..(y + 1) * self.width].iter()
+  }
+
+  #[allow(dead_code)]
+  pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+    self.cells.iter().skip(x).step_by(self.width.max(1))
+  }
+
+  // the up to four cells next to `(x, y)`.
+  #[allow(dead_code)]
+  pub fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize, &T)> {
+    DIRECTIONS
+      .iter()
+      .filter_map(move |&dir| self.step(x, y, dir))
+      .map(move |(nx, ny)| (nx, ny, &self.cells[ny * self.width + nx]))
+  }
+
+  // the cells from `(x, y)` (not included) to the edge of the grid towards `dir`.
+  pub fn ray(&self, x: usize, y: usize, dir: Direction) -> impl Iterator<Item = &T> {
+    std::iter::successors(self.step(x, y, dir), move |&(x, y)| self.step(x, y, dir))
+      .map(move |(x, y)| &self.cells[y * self.width + x])
+  }
+}
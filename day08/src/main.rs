@@ -1,109 +1,130 @@
-use std::collections::HashMap;
+mod grid;
+mod pnm;
+mod query;
+
+use grid::{Direction, Grid, ALL_DIRECTIONS, DIRECTIONS};
+use query::{Sightlines, TreeReport};
 use std::error::Error;
-use std::io::{self, Read, Write};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
 
 type Result<T> = ::std::result::Result<T, Box<dyn Error>>;
 
-fn main() -> Result<()> {
-  let mut input = String::new();
-  io::stdin().read_to_string(&mut input)?;
-  solve_part1(&input)?;
-  solve_part2(&input)?;
-  Ok(())
+// scenic scores too big for a `u64` stop here, see `Sweep::look`.
+const SCORE_OVERFLOW: u64 = u64::MAX;
+
+#[derive(Debug, Default)]
+struct Options {
+  random: Option<(usize, usize)>,
+  seed: u64,
+  verify: Option<usize>,
+  heatmap: Option<String>,
+  visible_map: Option<String>,
+  overlay: bool,
+  trees: Vec<(usize, usize)>,
+  top: Option<usize>,
+  sightlines: Sightlines,
+  spaced: bool, // heights are whitespace separated numbers, not packed digits.
 }
 
-// how many trees are visible from outside the grid?
-fn solve_part1(input: &str) -> Result<()> {
-  let mut res = 0;
-
-  let grids = input.lines().enumerate().fold(vec![], |mut acc, line| {
-    acc.push(
-      line
-        .1
-        .split("")
-        .filter(|x| !x.is_empty())
-        .map(|l| l.parse::<i32>().unwrap())
-        .collect::<Vec<i32>>(),
-    );
-    acc
-  });
+// per tree results of the sweeps, combined over every sightline direction:
+// whether any of them reaches the edge, and the product of the viewing
+// distances.
+#[derive(Debug)]
+struct Visibility {
+  visible: Grid<bool>,
+  scores: Grid<u64>,
+}
 
-  let mut map: HashMap<String, i32> = HashMap::new();
+fn main() -> Result<()> {
+  let opts = parse_options(std::env::args().skip(1))?;
 
-  grids
-    .iter()
-    .skip(1) // skip top edge
-    .enumerate()
-    .take_while(|(idx, _)| *idx < grids.len() - 2) // skip bottom edge.
-    .fold((0, Vec::new()), |mut acc, curr| {
-      // iter only interior trees.
-      curr
-        .1
-        .iter()
-        .skip(1) // skip left edge.
-        .enumerate()
-        .take_while(|(idx, _)| *idx < grids.len() - 2) // skip right edge.
-        .for_each(|(index_x, digit)| {
-          // individual interior tree.
-          let index_y = acc.0;
-          let current_x_idx = index_x + 1;
-          let current_y_idx = index_y + 1;
-
-          let mut upward_idx = index_y;
-          let mut downward_idx = index_y + 2;
-          let mut leftward_idx = current_x_idx - 1;
-          let mut rightward_idx = current_x_idx + 1;
-
-          // find downward
-          while downward_idx < grids.len() {
-            if digit <= &grids[downward_idx][current_x_idx] {
-              break;
-            }
-            downward_idx += 1;
-          }
+  if let Some(rounds) = opts.verify {
+    return verify_sweeps(rounds, opts.seed);
+  }
 
-          // find rightward
-          while rightward_idx < curr.1.len() {
-            if digit <= &curr.1[rightward_idx] {
-              break;
-            }
-            rightward_idx += 1;
-          }
+  let forest = match opts.random {
+    Some((width, height)) => random_forest(width, height, &mut Rng(opts.seed | 1)),
+    None => {
+      let mut input = String::new();
+      io::stdin().read_to_string(&mut input)?;
+      parse_forest(&input, opts.spaced)?
+    }
+  };
+  let visibility = sweep_visibility(&forest, &opts.sightlines);
 
-          // find leftward
-          while leftward_idx + 1 > 0 {
-            if digit <= &curr.1[leftward_idx] {
-              break;
-            }
-            leftward_idx -= 1;
-          }
+  if let Some(path) = &opts.heatmap {
+    write_heatmap(path, &visibility.scores)?;
+  }
+  if let Some(path) = &opts.visible_map {
+    let mut out = BufWriter::new(File::create(path)?);
+    pnm::write_pbm(&mut out, &visibility.visible)?;
+    out.flush()?;
+  }
+  if opts.overlay {
+    return print_overlay(&forest, &visibility);
+  }
+  if !opts.trees.is_empty() || opts.top.is_some() {
+    return print_queries(&forest, &visibility, &opts);
+  }
 
-          // find upward
-          while upward_idx + 1 > 0 {
-            if digit <= &grids[upward_idx][current_x_idx] {
-              break;
-            }
-            upward_idx -= 1;
-          }
+  solve_part1(&visibility)?;
+  solve_part2(&visibility)?;
+  Ok(())
+}
 
-          if downward_idx == grids.len()
-            || upward_idx + 1 == 0
-            || rightward_idx == curr.1.len()
-            || leftward_idx + 1 == 0
-          {
-            map
-              .entry(format!("[{current_x_idx:},{current_y_idx:}]"))
-              .and_modify(|counter| *counter += 1)
-              .or_insert(1);
-          }
-        });
+// usage: day08 [--random <width>x<height>] [--seed <n>] [--verify <rounds>]
+//              [--heatmap <file.pgm|file.ppm>] [--visible-map <file.pbm>] [--overlay]
+//              [--tree <x>,<y> ...] [--top <k>]
+//              [--sightlines cardinal|eight|<dir>,<dir>,...] [--see-over-equal]
+//              [--spaced]
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options> {
+  let mut opts = Options::default();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--random" => {
+        let value = next_value(&mut args, &arg)?;
+        let (width, height) = value.split_once('x').ok_or("expected <width>x<height>")?;
+        opts.random = Some((width.parse()?, height.parse()?));
+      }
+      "--seed" => opts.seed = next_value(&mut args, &arg)?.parse()?,
+      "--verify" => opts.verify = Some(next_value(&mut args, &arg)?.parse()?),
+      "--heatmap" => opts.heatmap = Some(next_value(&mut args, &arg)?),
+      "--visible-map" => opts.visible_map = Some(next_value(&mut args, &arg)?),
+      "--overlay" => opts.overlay = true,
+      "--tree" => {
+        let value = next_value(&mut args, &arg)?;
+        let (x, y) = value.split_once(',').ok_or("expected <x>,<y>")?;
+        opts.trees.push((x.parse()?, y.parse()?));
+      }
+      "--top" => opts.top = Some(next_value(&mut args, &arg)?.parse()?),
+      "--sightlines" => {
+        opts.sightlines.directions = match next_value(&mut args, &arg)?.as_str() {
+          "cardinal" => DIRECTIONS.to_vec(),
+          "eight" => ALL_DIRECTIONS.to_vec(),
+          list => list
+            .split(',')
+            .map(parse_direction)
+            .collect::<Result<Vec<Direction>>>()?,
+        }
+      }
+      "--see-over-equal" => opts.sightlines.see_over_equal = true,
+      "--spaced" => opts.spaced = true,
+      _ => return Err(format!("unknown option: {}", arg).into()),
+    }
+  }
+  Ok(opts)
+}
 
-      acc = (acc.0 + 1, curr.1.to_vec());
-      acc
-    });
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+  args
+    .next()
+    .ok_or_else(|| format!("missing value for {}", flag).into())
+}
 
-  let visible_edges_tree = (grids[0].len() * 2) + ((grids.len() - 2) * 2);
-  res = map.len() + visible_edges_tree;
+// how many trees are visible from outside the grid?
+fn solve_part1(visibility: &Visibility) -> Result<()> {
+  let res = visible_count(visibility);
 
   writeln!(io::stdout(), "{:?}", res)?;
   Ok(())
@@ -111,102 +132,404 @@ fn solve_part1(input: &str) -> Result<()> {
 
 // What is the highest scenic score possible for any tree?
 // test: 8
-fn solve_part2(input: &str) -> Result<()> {
-  let mut res = 0;
-
-  let grids = input.lines().enumerate().fold(vec![], |mut acc, line| {
-    acc.push(
-      line
-        .1
-        .split("")
-        .filter(|x| !x.is_empty())
-        .map(|l| l.parse::<i32>().unwrap())
-        .collect::<Vec<i32>>(),
-    );
-    acc
+fn solve_part2(visibility: &Visibility) -> Result<()> {
+  let res = best_score(visibility);
+  if res == SCORE_OVERFLOW {
+    return Err("the best scenic score doesn't fit in 64 bits".into());
+  }
+
+  writeln!(io::stdout(), "{:?}", res)?;
+  Ok(())
+}
+
+// scores span many orders of magnitude, so they are shaded on a log scale.
+// a `.ppm` file gets a colour ramp, anything else a grey PGM.
+fn write_heatmap(path: &str, scores: &Grid<u64>) -> Result<()> {
+  let max = scores.cells().iter().copied().max().unwrap_or(0);
+  let scale = (max as f64).ln_1p();
+  let levels = scores.map(|&score| match scale > 0.0 {
+    true => ((score as f64).ln_1p() / scale * 255.0).round() as u8,
+    false => 0,
   });
 
-  let mut map: HashMap<String, i32> = HashMap::new();
+  let mut out = BufWriter::new(File::create(path)?);
+  match path.ends_with(".ppm") {
+    true => pnm::write_ppm(&mut out, &levels.map(|&level| pnm::heat_colour(level)))?,
+    false => pnm::write_pgm(&mut out, &levels)?,
+  }
+  out.flush()?;
+  Ok(())
+}
+
+// `#` for a visible tree, `.` for a hidden one, `*` for the tree with the best view.
+fn print_overlay(forest: &Grid<u32>, visibility: &Visibility) -> Result<()> {
+  let best = query::top_trees(&visibility.scores, 1)
+    .first()
+    .map(|&(x, y, _)| (x, y));
+
+  let mut out = BufWriter::new(io::stdout());
+  for y in 0..forest.height() {
+    let line: String = (0..forest.width())
+      .map(|x| match visibility.visible.get(x, y) {
+        _ if best == Some((x, y)) => '*',
+        Some(true) => '#',
+        _ => '.',
+      })
+      .collect();
+    writeln!(out, "{}", line)?;
+  }
+  Ok(())
+}
+
+fn print_queries(forest: &Grid<u32>, visibility: &Visibility, opts: &Options) -> Result<()> {
+  let mut out = io::stdout();
+  for &(x, y) in &opts.trees {
+    let report = query::tree_report(forest, &opts.sightlines, x, y).ok_or_else(|| {
+      format!(
+        "({}, {}) is outside the {}x{} forest",
+        x,
+        y,
+        forest.width(),
+        forest.height()
+      )
+    })?;
+    print_report(&mut out, &opts.sightlines, &report)?;
+  }
+
+  if let Some(k) = opts.top {
+    for (rank, (x, y, score)) in query::top_trees(&visibility.scores, k)
+      .into_iter()
+      .enumerate()
+    {
+      writeln!(
+        out,
+        "{:>3}. ({}, {}) height {} score {}",
+        rank + 1,
+        x,
+        y,
+        forest.get(x, y).unwrap(),
+        score_text(score)
+      )?;
+    }
+  }
+  Ok(())
+}
+
+fn print_report(out: &mut impl Write, sightlines: &Sightlines, report: &TreeReport) -> Result<()> {
+  writeln!(
+    out,
+    "({}, {}) height {}, {}, score {}",
+    report.x,
+    report.y,
+    report.height,
+    match report.is_visible() {
+      true => "visible",
+      false => "hidden",
+    },
+    score_text(report.scenic_score())
+  )?;
+  for (i, &dir) in sightlines.directions.iter().enumerate() {
+    writeln!(
+      out,
+      "  {:<10} {:<7} sees {}",
+      direction_name(dir),
+      match report.visible_from[i] {
+        true => "visible",
+        false => "hidden",
+      },
+      report.viewing_distance[i]
+    )?;
+  }
+  Ok(())
+}
+
+// compares the sweeps with walking every ray by hand, on small random forests
+// and with random sightline rules.
+fn verify_sweeps(rounds: usize, seed: u64) -> Result<()> {
+  let mut rng = Rng(seed | 1);
+  for round in 0..rounds {
+    let width = 1 + rng.below(20) as usize;
+    let height = 1 + rng.below(20) as usize;
+    let forest = random_forest(width, height, &mut rng);
+    let sightlines = Sightlines {
+      directions: ALL_DIRECTIONS
+        .into_iter()
+        .filter(|_| rng.below(2) == 0)
+        .collect(),
+      see_over_equal: rng.below(2) == 0,
+    };
+    let visibility = sweep_visibility(&forest, &sightlines);
+
+    for (x, y) in forest.positions() {
+      let report = query::tree_report(&forest, &sightlines, x, y).unwrap();
+      let (visible, score) = (report.is_visible(), report.scenic_score());
+      if visibility.visible.get(x, y) != Some(&visible)
+        || visibility.scores.get(x, y) != Some(&score)
+      {
+        return Err(
+          format!(
+            "round {}: {}x{} forest differs at ({}, {})",
+            round, width, height, x, y
+          )
+          .into(),
+        );
+      }
+    }
+  }
 
-  grids
-    .iter()
-    .skip(1) // skip top edge
+  writeln!(io::stdout(), "ok, {} forests match", rounds)?;
+  Ok(())
+}
+
+// ---------------------- UTILS -------------------------
+
+fn visible_count(visibility: &Visibility) -> usize {
+  visibility.visible.cells().iter().filter(|&&v| v).count()
+}
+
+fn best_score(visibility: &Visibility) -> u64 {
+  visibility.scores.cells().iter().copied().max().unwrap_or(0)
+}
+
+fn score_text(score: u64) -> String {
+  match score {
+    SCORE_OVERFLOW => "overflow".to_string(),
+    _ => score.to_string(),
+  }
+}
+
+// rows are packed digits, or whitespace separated numbers with `spaced`. a
+// single column looks the same either way, so it's never guessed.
+fn parse_forest(input: &str, spaced: bool) -> Result<Grid<u32>> {
+  let rows = input
+    .lines()
+    .map(str::trim)
     .enumerate()
-    .take_while(|(idx, _)| *idx < grids.len() - 2) // skip bottom edge.
-    .fold((0, Vec::new()), |mut acc, curr| {
-      // iter only interior trees.
-      curr
-        .1
-        .iter()
-        .skip(1) // skip left edge.
-        .enumerate()
-        .take_while(|(idx, _)| *idx < grids.len() - 2) // skip right edge.
-        .for_each(|(index_x, digit)| {
-          // individual interior tree.
-          let index_y = acc.0;
-          let current_x_idx = index_x + 1;
-          let current_y_idx = index_y + 1;
-
-          let mut upward_idx = index_y;
-          let mut downward_idx = index_y + 2;
-          let mut leftward_idx = current_x_idx - 1;
-          let mut rightward_idx = current_x_idx + 1;
-
-          let mut count_upward = 0;
-          let mut count_downward = 0;
-          let mut count_leftward = 0;
-          let mut count_rightward = 0;
-
-          // find downward
-          while downward_idx < grids.len() {
-            count_downward += 1;
-            if digit <= &grids[downward_idx][current_x_idx] {
-              break;
-            }
-            downward_idx += 1;
-          }
+    .filter(|(_, line)| !line.is_empty())
+    .map(
+      |(idx, line)| match (spaced, line.contains(char::is_whitespace)) {
+        (true, _) => line
+          .split_whitespace()
+          .map(|tree| {
+            tree
+              .parse()
+              .map_err(|_| format!("line {}: not a tree height: {}", idx + 1, tree))
+          })
+          .collect::<std::result::Result<Vec<u32>, String>>(),
+        (false, true) => Err(format!(
+          "line {}: heights separated by spaces, use --spaced",
+          idx + 1
+        )),
+        (false, false) => line
+          .chars()
+          .map(|c| {
+            c.to_digit(10)
+              .ok_or_else(|| format!("line {}: not a tree height: {}", idx + 1, c))
+          })
+          .collect::<std::result::Result<Vec<u32>, String>>(),
+      },
+    )
+    .collect::<std::result::Result<Vec<Vec<u32>>, String>>()?;
 
-          // find rightward
-          while rightward_idx < curr.1.len() {
-            count_rightward += 1;
-            if digit <= &curr.1[rightward_idx] {
-              break;
-            }
-            rightward_idx += 1;
-          }
+  Grid::from_rows(rows)
+}
 
-          // find leftward
-          while leftward_idx + 1 > 0 {
-            count_leftward += 1;
-            if digit <= &curr.1[leftward_idx] {
-              break;
-            }
-            leftward_idx -= 1;
-          }
+// one pass per direction with a monotonic stack per line of sight: walking a
+// line away from the edge it looks at, its stack holds the trees that could
+// still block the view of a later tree, nearest on top. anything that can't
+// block the current tree can't block anything behind it either and is popped,
+// so each tree is pushed and popped once per direction.
+// the forest is always walked row by row so memory is read in order: sideways
+// lines are whole rows, anything else moves one row per step and keeps a
+// stack per line, indexed by where the line crosses the row.
+fn sweep_visibility(forest: &Grid<u32>, sightlines: &Sightlines) -> Visibility {
+  let (width, height) = (forest.width(), forest.height());
+  let mut sweep = Sweep {
+    trees: forest.cells(),
+    sightlines,
+    visible: vec![false; width * height],
+    scores: vec![1u64; width * height],
+  };
+
+  for &dir in &sightlines.directions {
+    let (dx, dy) = dir.delta();
+    let stride = dy * width as isize + dx;
+    // the row sweeps start from, and how far each column is from the
+    // column edge `dir` looks at.
+    let first_row = |j: usize| if dy > 0 { height - 1 - j } else { j };
+    let to_column_edge = |x: usize| match dx {
+      1 => width - 1 - x,
+      -1 => x,
+      _ => usize::MAX,
+    };
 
-          // find upward
-          while upward_idx + 1 > 0 {
-            count_upward += 1;
-            if digit <= &grids[upward_idx][current_x_idx] {
-              break;
-            }
-            upward_idx -= 1;
+    match dy {
+      0 => {
+        let mut stack = Vec::with_capacity(width);
+        for y in 0..height {
+          stack.clear();
+          for j in 0..width {
+            let x = if dx > 0 { width - 1 - j } else { j };
+            sweep.look(&mut stack, y * width + x, j as u32, j, stride);
           }
+        }
+      }
+      _ => {
+        // lines going straight up or down are columns, diagonal ones cross
+        // row `j` at `x + dx * j`, shifted so it's never negative.
+        let diagonals = dx.unsigned_abs() * height.saturating_sub(1);
+        let shift = if dx < 0 { diagonals as isize } else { 0 };
+        let mut stacks = vec![Vec::new(); width + diagonals];
+        for j in 0..height {
+          let y = first_row(j);
+          for x in 0..width {
+            let line = (x as isize + dx * j as isize + shift) as usize;
+            let edge = j.min(to_column_edge(x));
+            sweep.look(&mut stacks[line], y * width + x, j as u32, edge, stride);
+          }
+        }
+      }
+    }
+  }
 
-          let scenic_score = count_downward * count_leftward * count_rightward * count_upward;
-          map.insert(format!("[{current_x_idx:},{current_y_idx:}]"), scenic_score);
-        });
+  Visibility {
+    visible: Grid::new(width, height, sweep.visible).unwrap(),
+    scores: Grid::new(width, height, sweep.scores).unwrap(),
+  }
+}
 
-      acc = (acc.0 + 1, curr.1.to_vec());
-      acc
-    });
+// what the sweeps have found so far.
+struct Sweep<'a> {
+  trees: &'a [u32],
+  sightlines: &'a Sightlines,
+  visible: Vec<bool>,
+  scores: Vec<u64>,
+}
 
-  res = *map
-    .iter()
-    .max_by(|a, b| a.1.cmp(b.1))
-    .map(|(_, v)| v)
-    .unwrap();
+impl Sweep<'_> {
+  // the tree at `at` is `j` steps along its line and `edge` steps from the
+  // edge it looks at. the stack holds the steps of the trees before it on
+  // the same line, the one `n` steps back is at `at + n * stride`.
+  #[inline]
+  fn look(&mut self, stack: &mut Vec<u32>, at: usize, j: u32, edge: usize, stride: isize) {
+    let tree = self.trees[at];
+    let behind = |k: u32| (at as isize + (j - k) as isize * stride) as usize;
+    while let Some(&top) = stack.last() {
+      if self.sightlines.blocks(self.trees[behind(top)], tree) {
+        break;
+      }
+      stack.pop();
+    }
+    let distance = match stack.last() {
+      Some(&top) => (j - top) as u64,
+      None => {
+        self.visible[at] = true;
+        edge as u64
+      }
+    };
+    self.scores[at] = self.scores[at].saturating_mul(distance);
+    stack.push(j);
+  }
+}
 
-  writeln!(io::stdout(), "{:?}", res)?;
-  Ok(())
+fn parse_direction(name: &str) -> Result<Direction> {
+  ALL_DIRECTIONS
+    .into_iter()
+    .find(|&dir| direction_name(dir) == name)
+    .ok_or_else(|| format!("unknown direction: {}", name).into())
+}
+
+fn direction_name(dir: Direction) -> &'static str {
+  match dir {
+    Direction::Up => "up",
+    Direction::Down => "down",
+    Direction::Left => "left",
+    Direction::Right => "right",
+    Direction::UpLeft => "up-left",
+    Direction::UpRight => "up-right",
+    Direction::DownLeft => "down-left",
+    Direction::DownRight => "down-right",
+  }
+}
+
+fn random_forest(width: usize, height: usize, rng: &mut Rng) -> Grid<u32> {
+  let cells = (0..width * height).map(|_| rng.below(10) as u32).collect();
+  Grid::new(width, height, cells).unwrap()
+}
+
+// xorshift, good enough for making up forests.
+struct Rng(u64);
+
+impl Rng {
+  fn below(&mut self, n: u64) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0 % n
+  }
+}
+
+// ---------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn answers(input: &str) -> (usize, u64) {
+    let forest = parse_forest(input, false).unwrap();
+    let visibility = sweep_visibility(&forest, &Sightlines::default());
+    (visible_count(&visibility), best_score(&visibility))
+  }
+
+  #[test]
+  fn sample() {
+    assert_eq!(answers(include_str!("../input/test.txt")), (21, 8));
+  }
+
+  // 7 wide, 23 tall.
+  #[test]
+  fn tall_forest() {
+    assert_eq!(answers(include_str!("../input/tall.txt")), (101, 768));
+  }
+
+  // 31 wide, 6 tall.
+  #[test]
+  fn wide_forest() {
+    assert_eq!(answers(include_str!("../input/wide.txt")), (132, 320));
+  }
+
+  // eight ways over equal trees, every tree sees to the edge in every direction.
+  #[test]
+  fn flat_forest() {
+    let sightlines = Sightlines {
+      directions: ALL_DIRECTIONS.to_vec(),
+      see_over_equal: true,
+    };
+    let small = Grid::new(5, 5, vec![0; 25]).unwrap();
+    let visibility = sweep_visibility(&small, &sightlines);
+    assert_eq!(best_score(&visibility), 1 << 8);
+
+    let large = Grid::new(600, 600, vec![0; 600 * 600]).unwrap();
+    let visibility = sweep_visibility(&large, &sightlines);
+    assert_eq!(best_score(&visibility), SCORE_OVERFLOW);
+    let report = query::tree_report(&large, &sightlines, 300, 300).unwrap();
+    assert_eq!(report.scenic_score(), SCORE_OVERFLOW);
+    assert!(solve_part2(&visibility).is_err());
+  }
+
+  #[test]
+  fn spaced_heights() {
+    let column = parse_forest("10\n12\n", true).unwrap();
+    assert_eq!((column.width(), column.height()), (1, 2));
+    assert_eq!(column.cells(), &[10, 12]);
+    assert_eq!(parse_forest("10\n12\n", false).unwrap().width(), 2);
+    assert!(parse_forest("30373\n2 5 5 1 2\n", false).is_err());
+    assert!(parse_forest("3 0\n12\n", true).is_err());
+  }
+
+  // random forests and sightlines against walking every ray by hand.
+  #[test]
+  fn sweeps_match_brute_force() {
+    verify_sweeps(500, 42).unwrap();
+  }
 }
@@ -0,0 +1,38 @@
+use crate::grid::Grid;
+use std::io::{self, Write};
+
+// just enough of the netpbm formats to look at a grid, one pixel per cell.
+// everything is written in the binary variants (P4, P5, P6) with maxval 255.
+
+// black and white, a set bit is black. rows are padded to whole bytes.
+pub fn write_pbm(out: &mut impl Write, image: &Grid<bool>) -> io::Result<()> {
+  writeln!(out, "P4\n{} {}", image.width(), image.height())?;
+  for y in 0..image.height() {
+    let mut row = vec![0u8; image.width().div_ceil(8)];
+    for (x, &set) in image.row(y).enumerate() {
+      if set {
+        row[x / 8] |= 0x80 >> (x % 8);
+      }
+    }
+    out.write_all(&row)?;
+  }
+  Ok(())
+}
+
+pub fn write_pgm(out: &mut impl Write, image: &Grid<u8>) -> io::Result<()> {
+  writeln!(out, "P5\n{} {}\n255", image.width(), image.height())?;
+  out.write_all(image.cells())
+}
+
+pub fn write_ppm(out: &mut impl Write, image: &Grid<[u8; 3]>) -> io::Result<()> {
+  writeln!(out, "P6\n{} {}\n255", image.width(), image.height())?;
+  let bytes: Vec<u8> = image.cells().iter().flatten().copied().collect();
+  out.write_all(&bytes)
+}
+
+// black -> red -> yellow -> white, so brighter is hotter in colour too.
+pub fn heat_colour(level: u8) -> [u8; 3] {
+  let t = level as u32 * 3;
+  let channel = |from: u32| (t.saturating_sub(from).min(255)) as u8;
+  [channel(0), channel(255), channel(510)]
+}
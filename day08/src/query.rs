@@ -0,0 +1,121 @@
+use crate::grid::{Direction, Grid, DIRECTIONS};
+
+// which way trees look, and whether a tree of the same height is in the way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sightlines {
+  pub directions: Vec<Direction>,
+  pub see_over_equal: bool,
+}
+
+// everything there is to know about one tree, per direction in `Sightlines` order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeReport {
+  pub x: usize,
+  pub y: usize,
+  pub height: u32,
+  pub visible_from: Vec<bool>,
+  pub viewing_distance: Vec<usize>,
+}
+
+impl Default for Sightlines {
+  fn default() -> Self {
+    Sightlines {
+      directions: DIRECTIONS.to_vec(),
+      see_over_equal: false,
+    }
+  }
+}
+
+impl Sightlines {
+  // does `tree` stop the view of a tree `height` tall?
+  pub fn blocks(&self, tree: u32, height: u32) -> bool {
+    match self.see_over_equal {
+      true => tree > height,
+      false => tree >= height,
+    }
+  }
+}
+
+impl TreeReport {
+  pub fn is_visible(&self) -> bool {
+    self.visible_from.iter().any(|&visible| visible)
+  }
+
+  // stuck at `u64::MAX` once it doesn't fit any more.
+  pub fn scenic_score(&self) -> u64 {
+    self
+      .viewing_distance
+      .iter()
+      .fold(1, |score: u64, &d| score.saturating_mul(d as u64))
+  }
+}
+
+// walks every sightline from `(x, y)`, none if that's outside the forest.
+pub fn tree_report(
+  forest: &Grid<u32>,
+  sightlines: &Sightlines,
+  x: usize,
+  y: usize,
+) -> Option<TreeReport> {
+  let &height = forest.get(x, y)?;
+  let dirs = &sightlines.directions;
+  Some(TreeReport {
+    x,
+    y,
+    height,
+    visible_from: dirs
+      .iter()
+      .map(|&dir| visible_from(forest, sightlines, x, y, dir))
+      .collect(),
+    viewing_distance: dirs
+      .iter()
+      .map(|&dir| viewing_distance(forest, sightlines, x, y, dir))
+      .collect(),
+  })
+}
+
+// the `k` best views as `(x, y, score)`, ties broken row by row.
+pub fn top_trees(scores: &Grid<u64>, k: usize) -> Vec<(usize, usize, u64)> {
+  let mut ranking: Vec<(usize, usize, u64)> = scores
+    .positions()
+    .map(|(x, y)| (x, y, *scores.get(x, y).unwrap()))
+    .collect();
+  ranking.sort_by_key(|&(x, y, score)| (std::cmp::Reverse(score), y, x));
+  ranking.truncate(k);
+  ranking
+}
+
+// a tree is visible from a side if nothing between it and that edge blocks it.
+// trees on the edge have nothing in the way at all.
+pub fn visible_from(
+  forest: &Grid<u32>,
+  sightlines: &Sightlines,
+  x: usize,
+  y: usize,
+  dir: Direction,
+) -> bool {
+  let &height = forest.get(x, y).unwrap();
+  forest
+    .ray(x, y, dir)
+    .all(|&tree| !sightlines.blocks(tree, height))
+}
+
+// how many trees can be seen in one direction, up to and including the first
+// one that blocks the view.
+pub fn viewing_distance(
+  forest: &Grid<u32>,
+  sightlines: &Sightlines,
+  x: usize,
+  y: usize,
+  dir: Direction,
+) -> usize {
+  let &height = forest.get(x, y).unwrap();
+  let mut count = 0;
+  for &tree in forest.ray(x, y, dir) {
+    count += 1;
+    if sightlines.blocks(tree, height) {
+      break;
+    }
+  }
+  count
+}
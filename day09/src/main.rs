@@ -1,280 +1,713 @@
-use std::collections::BTreeSet;
+use std::collections::HashSet;
 use std::error::Error;
-use std::io::{self, Read, Write};
-use std::iter;
+use std::io::{self, BufWriter, Read, Write};
+use std::thread;
+use std::time::Duration;
 
 type Result<T> = ::std::result::Result<T, Box<dyn Error>>;
 
+// one line of input: move the head `steps` times by `delta`.
 #[derive(Debug, Clone)]
-enum Direction {
-  Left(i32),
-  Right(i32),
-  Up(i32),
-  Down(i32),
-  Unsupported,
+struct Move<const D: usize> {
+  name: String, // as written, eg. `UR`.
+  delta: Position<D>,
+  steps: u32,
 }
 
+// a rope of `knots.len()` knots, the head first. every knot follows the one
+// in front of it and `visited` records where the last one has been.
 #[derive(Debug, Clone)]
-struct Rope {
-  head: Position,
-  tail: Position,
-  starting_point: Position,
-  visited: BTreeSet<String>,
+struct Rope<const D: usize> {
+  knots: Vec<Position<D>>,
+  visited: HashSet<Position<D>>,
+}
+
+// steps through the moves one unit step at a time. as an iterator it yields a
+// `Snapshot` after every step, `advance` does the same without copying knots.
+#[derive(Debug, Clone)]
+struct RopeSim<'a, const D: usize> {
+  moves: &'a [Move<D>],
+  rope: Rope<D>,
+  instruction: usize, // index of the move being made.
+  done: u32,          // steps of that move taken so far.
+  step: u64,          // steps taken in total.
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot<const D: usize> {
+  step: u64,
+  instruction: usize,
+  instruction_step: u32, // 1 after the first step of a move.
+  knots: Vec<Position<D>>,
+}
+
+// x and y on the plane (up is -y), plus z for 3D ropes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Position<const D: usize>([i32; D]);
+
+// the part of space that gets drawn, inclusive on both ends.
+#[derive(Debug, Clone, Copy)]
+struct Viewport<const D: usize> {
+  min: Position<D>,
+  max: Position<D>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RenderMode {
+  Moves, // after every instruction.
+  Steps, // after every single step.
+}
+
+// what one knot did over a whole run, see `knot_stats`.
+#[derive(Debug, Clone)]
+struct KnotStats<const D: usize> {
+  visited: HashSet<Position<D>>,
+  bounds: Viewport<D>,
+  distance: u64, // steps taken, a diagonal step counts as one.
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-struct Position {
-  x: i32,
-  y: i32,
+enum CsvTable {
+  Stats,  // one row per knot.
+  Visits, // every cell each knot visited.
+  Shared, // the cells visited by every knot.
+}
+
+#[derive(Debug)]
+struct Options {
+  knots: Option<usize>,
+  verify: bool,
+  random: Option<usize>,
+  seed: u64,
+  render: Option<RenderMode>,
+  animate: bool,
+  fps: f64,
+  map: bool,
+  csv: Option<CsvTable>,
+  three_d: bool,
+  seek: Option<u64>,
+}
+
+impl<const D: usize> Position<D> {
+  fn origin() -> Self {
+    Position([0; D])
+  }
+
+  // chebyshev, knots touch when this is at most 1.
+  fn distance(&self, other: &Position<D>) -> u32 {
+    self
+      .0
+      .iter()
+      .zip(other.0)
+      .map(|(a, b)| a.abs_diff(b))
+      .max()
+      .unwrap_or(0)
+  }
+
+  fn coords(&self) -> String {
+    self.0.map(|c| c.to_string()).join(",")
+  }
 }
 
-impl Rope {
-  fn new() -> Self {
+impl<const D: usize> Rope<D> {
+  fn new(len: usize) -> Self {
+    let start = Position::origin();
     Rope {
-      head: Position { x: 0, y: 0 },
-      tail: Position { x: 0, y: 0 },
-      starting_point: Position { x: 0, y: 0 },
-      visited: BTreeSet::new(),
+      knots: vec![start; len.max(1)],
+      visited: HashSet::from([start]),
+    }
+  }
+
+  // moves the head one step and lets the rest of the rope catch up.
+  fn step(&mut self, delta: Position<D>) {
+    for (c, d) in self.knots[0].0.iter_mut().zip(delta.0) {
+      *c += d;
+    }
+    for idx in 1..self.knots.len() {
+      let head = self.knots[idx - 1];
+      if !move_tail(&mut self.knots[idx], head) {
+        // nothing further down the rope can move either.
+        break;
+      }
     }
+
+    let tail = self.knots[self.knots.len() - 1];
+    self.visited.insert(tail);
   }
 }
 
-fn parse(cmd: &str) -> Direction {
-  let c = cmd.split(' ').collect::<Vec<_>>();
-  let move_steps = c[1].parse::<i32>().unwrap();
+impl<'a, const D: usize> RopeSim<'a, D> {
+  fn new(moves: &'a [Move<D>], knots: usize) -> Self {
+    RopeSim {
+      moves,
+      rope: Rope::new(knots),
+      instruction: 0,
+      done: 0,
+      step: 0,
+    }
+  }
+
+  fn rope(&self) -> &Rope<D> {
+    &self.rope
+  }
+
+  fn into_rope(self) -> Rope<D> {
+    self.rope
+  }
+
+  // takes one step, false once every move is done.
+  fn advance(&mut self) -> bool {
+    while let Some(m) = self.moves.get(self.instruction) {
+      if self.done < m.steps {
+        self.rope.step(m.delta);
+        self.done += 1;
+        self.step += 1;
+        return true;
+      }
+      self.instruction += 1;
+      self.done = 0;
+    }
+    false
+  }
 
-  match c[0] {
-    "R" => Direction::Right(move_steps),
-    "L" => Direction::Left(move_steps),
-    "U" => Direction::Up(move_steps),
-    "D" => Direction::Down(move_steps),
-    _ => Direction::Unsupported,
+  // the state after `step` steps, starting over if that's already behind us.
+  // none if the moves run out first.
+  fn seek(&mut self, step: u64) -> Option<Snapshot<D>> {
+    if step < self.step {
+      *self = RopeSim::new(self.moves, self.rope.knots.len());
+    }
+    while self.step < step {
+      if !self.advance() {
+        return None;
+      }
+    }
+    Some(self.snapshot())
+  }
+
+  fn snapshot(&self) -> Snapshot<D> {
+    Snapshot {
+      step: self.step,
+      instruction: self.instruction,
+      instruction_step: self.done,
+      knots: self.rope.knots.clone(),
+    }
+  }
+}
+
+impl<const D: usize> Iterator for RopeSim<'_, D> {
+  type Item = Snapshot<D>;
+
+  fn next(&mut self) -> Option<Snapshot<D>> {
+    self.advance().then(|| self.snapshot())
+  }
+}
+
+impl<const D: usize> Viewport<D> {
+  fn include(&mut self, pos: Position<D>) {
+    for axis in 0..D {
+      self.min.0[axis] = self.min.0[axis].min(pos.0[axis]);
+      self.max.0[axis] = self.max.0[axis].max(pos.0[axis]);
+    }
   }
 }
 
+impl Viewport<2> {
+  // one character per cell, rows top to bottom.
+  fn draw(&self, mut cell: impl FnMut(Position<2>) -> char) -> String {
+    (self.min.0[1]..=self.max.0[1])
+      .map(|y| {
+        (self.min.0[0]..=self.max.0[0])
+          .map(|x| cell(Position([x, y])))
+          .collect::<String>()
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+}
+
+// `R`/`L` move along x, `U`/`D` along y and `F`/`B` along z, which only
+// exists for 3D ropes. letters combine into diagonals, eg. `UR` or `DLF`.
+fn parse<const D: usize>(cmd: &str) -> Result<Move<D>> {
+  let (name, steps) = cmd
+    .split_once(' ')
+    .ok_or_else(|| format!("expected <direction> <steps>: {}", cmd))?;
+  let steps = steps
+    .trim()
+    .parse::<u32>()
+    .map_err(|_| format!("not a step count: {}", steps))?;
+  if name.is_empty() {
+    return Err(format!("missing direction: {}", cmd).into());
+  }
+
+  let mut delta = Position::origin();
+  for c in name.chars() {
+    let (axis, sign) = match c {
+      'R' => (0, 1),
+      'L' => (0, -1),
+      'U' => (1, -1),
+      'D' => (1, 1),
+      'F' => (2, 1),
+      'B' => (2, -1),
+      _ => return Err(format!("unknown direction: {}", name).into()),
+    };
+    if axis >= D {
+      return Err(format!("{} needs a 3D rope (--3d)", name).into());
+    }
+    if delta.0[axis] != 0 {
+      return Err(format!("{} moves along the same axis twice", name).into());
+    }
+    delta.0[axis] = sign;
+  }
+
+  Ok(Move {
+    name: name.to_string(),
+    delta,
+    steps,
+  })
+}
+
 fn main() -> Result<()> {
+  let opts = parse_options(std::env::args().skip(1))?;
+  let shows_motion = opts.render.is_some() || opts.animate || opts.map;
+
+  if opts.three_d {
+    if shows_motion {
+      return Err("only 2D ropes can be drawn".into());
+    }
+    return run(&load_moves::<3>(&opts)?, &opts);
+  }
+
+  let moves = load_moves::<2>(&opts)?;
+  if shows_motion {
+    return show_motion(&moves, opts.knots.unwrap_or(10), &opts);
+  }
+  run(&moves, &opts)
+}
+
+fn load_moves<const D: usize>(opts: &Options) -> Result<Vec<Move<D>>> {
+  if let Some(count) = opts.random {
+    return Ok(random_moves(count, &mut Rng(opts.seed | 1)));
+  }
+
   let mut input = String::new();
   io::stdin().read_to_string(&mut input)?;
-  solve_part1(&input)?;
-  solve_part2(&input)?;
+  input
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty())
+    .map(|(idx, line)| parse(line).map_err(|e| format!("line {}: {}", idx + 1, e).into()))
+    .collect()
+}
+
+fn run<const D: usize>(moves: &[Move<D>], opts: &Options) -> Result<()> {
+  if opts.verify {
+    return verify_ropes(moves);
+  }
+  if let Some(table) = opts.csv {
+    return write_csv(&knot_stats(moves, opts.knots.unwrap_or(10)), table);
+  }
+  if let Some(step) = opts.seek {
+    return print_snapshot(moves, opts.knots.unwrap_or(10), step);
+  }
+  if let Some(knots) = opts.knots {
+    writeln!(io::stdout(), "{:?}", simulate(moves, knots).visited.len())?;
+    return Ok(());
+  }
+
+  solve_part1(moves)?;
+  solve_part2(moves)?;
   Ok(())
 }
 
+// usage: day09 [--knots <n>] [--verify] [--random <moves>] [--seed <n>]
+//              [--render moves|steps] [--animate] [--fps <n>] [--map]
+//              [--csv stats|visits|shared] [--3d] [--seek <step>]
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options> {
+  let mut opts = Options {
+    knots: None,
+    verify: false,
+    random: None,
+    seed: 0,
+    render: None,
+    animate: false,
+    fps: 10.0,
+    map: false,
+    csv: None,
+    three_d: false,
+    seek: None,
+  };
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--knots" => opts.knots = Some(next_value(&mut args, &arg)?.parse()?),
+      "--verify" => opts.verify = true,
+      "--random" => opts.random = Some(next_value(&mut args, &arg)?.parse()?),
+      "--seed" => opts.seed = next_value(&mut args, &arg)?.parse()?,
+      "--render" => {
+        opts.render = match next_value(&mut args, &arg)?.as_str() {
+          "moves" => Some(RenderMode::Moves),
+          "steps" => Some(RenderMode::Steps),
+          mode => return Err(format!("unknown render mode: {}", mode).into()),
+        }
+      }
+      "--animate" => opts.animate = true,
+      "--fps" => opts.fps = next_value(&mut args, &arg)?.parse()?,
+      "--map" => opts.map = true,
+      "--csv" => {
+        opts.csv = match next_value(&mut args, &arg)?.as_str() {
+          "stats" => Some(CsvTable::Stats),
+          "visits" => Some(CsvTable::Visits),
+          "shared" => Some(CsvTable::Shared),
+          table => return Err(format!("unknown csv table: {}", table).into()),
+        }
+      }
+      "--3d" => opts.three_d = true,
+      "--seek" => opts.seek = Some(next_value(&mut args, &arg)?.parse()?),
+      _ => return Err(format!("unknown option: {}", arg).into()),
+    }
+  }
+  Ok(opts)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+  args
+    .next()
+    .ok_or_else(|| format!("missing value for {}", flag).into())
+}
+
 // How many positions does the tail of the rope visit at least once?
-fn solve_part1(input: &str) -> Result<()> {
-  let mut res = 0;
-  let mut rope_state = Rope::new();
-  rope_state.visited.insert(format!(
-    "[{},{}]",
-    rope_state.starting_point.x, rope_state.starting_point.y
-  ));
-
-  input.lines().for_each(|line| {
-    match parse(line) {
-      Direction::Up(val) => iter::repeat(1)
-        .take(val.try_into().unwrap())
-        .for_each(|val| {
-          rope_state.head.y -= val;
-          move_tail(
-            &mut rope_state.tail,
-            &mut rope_state.head,
-            &mut rope_state.visited,
-          );
-        }),
-      Direction::Down(val) => iter::repeat(1)
-        .take(val.try_into().unwrap())
-        .for_each(|val| {
-          rope_state.head.y += val;
-          move_tail(
-            &mut rope_state.tail,
-            &mut rope_state.head,
-            &mut rope_state.visited,
-          );
-        }),
-      Direction::Right(val) => iter::repeat(1)
-        .take(val.try_into().unwrap())
-        .for_each(|val| {
-          rope_state.head.x += val;
-          move_tail(
-            &mut rope_state.tail,
-            &mut rope_state.head,
-            &mut rope_state.visited,
-          );
-        }),
-      Direction::Left(val) => iter::repeat(1)
-        .take(val.try_into().unwrap())
-        .for_each(|val| {
-          rope_state.head.x -= val;
-          move_tail(
-            &mut rope_state.tail,
-            &mut rope_state.head,
-            &mut rope_state.visited,
-          );
-        }),
-      Direction::Unsupported => {}
-    };
-  });
+fn solve_part1<const D: usize>(moves: &[Move<D>]) -> Result<()> {
+  let res = simulate(moves, 2).visited.len();
 
-  res = rope_state.visited.len();
   writeln!(io::stdout(), "{:?}", res)?;
   Ok(())
 }
 
 // How many positions does the tail of the rope (10 knots 0-9) visit at least once?
-fn solve_part2(input: &str) -> Result<()> {
-  let mut res = 0;
-  let mut rope_state_h_1 = Rope::new();
-  let mut rope_state_2_3 = Rope::new();
-  let mut rope_state_4_5 = Rope::new();
-  let mut rope_state_6_7 = Rope::new();
-  let mut rope_state_8_9 = Rope::new();
-
-  let mut rope_vec = [
-    &mut rope_state_h_1,
-    &mut rope_state_2_3,
-    &mut rope_state_4_5,
-    &mut rope_state_6_7,
-    &mut rope_state_8_9,
-  ];
-
-  rope_vec.iter_mut().for_each(|rope_state| {
-    rope_state.visited.insert(format!(
-      "[{},{}]",
-      rope_state.starting_point.x, rope_state.starting_point.y
-    ));
-  });
+fn solve_part2<const D: usize>(moves: &[Move<D>]) -> Result<()> {
+  let res = simulate(moves, 10).visited.len();
 
-  input.lines().for_each(|line| {
-    match parse(line) {
-      Direction::Up(val) => iter::repeat(1)
-        .take(val.try_into().unwrap())
-        .for_each(|val| {
-          // move first  (H,1)
-          rope_vec[0].head.y -= val;
-          move_tail(
-            &mut rope_vec[0].tail,
-            &mut rope_vec[0].head,
-            &mut rope_vec[0].visited,
-          );
-
-          // move others knots sequencially  (2,3) ~ (8,9)
-          move_knots(&mut rope_vec);
-        }),
-      Direction::Down(val) => iter::repeat(1)
-        .take(val.try_into().unwrap())
-        .for_each(|val| {
-          rope_vec[0].head.y += val;
-          move_tail(
-            &mut rope_vec[0].tail,
-            &mut rope_vec[0].head,
-            &mut rope_vec[0].visited,
-          );
-          move_knots(&mut rope_vec);
-        }),
-      Direction::Right(val) => iter::repeat(1)
-        .take(val.try_into().unwrap())
-        .for_each(|val| {
-          rope_vec[0].head.x += val;
-          move_tail(
-            &mut rope_vec[0].tail,
-            &mut rope_vec[0].head,
-            &mut rope_vec[0].visited,
-          );
-          move_knots(&mut rope_vec);
-        }),
-      Direction::Left(val) => iter::repeat(1)
-        .take(val.try_into().unwrap())
-        .for_each(|val| {
-          rope_vec[0].head.x -= val;
-          move_tail(
-            &mut rope_vec[0].tail,
-            &mut rope_vec[0].head,
-            &mut rope_vec[0].visited,
-          );
-          move_knots(&mut rope_vec);
-        }),
-      Direction::Unsupported => {}
-    };
-  });
+  writeln!(io::stdout(), "{:?}", res)?;
+  Ok(())
+}
 
-  res = rope_vec[4].visited.len();
+// checks every rope length from 1 to 100 against `reference_visits`.
+fn verify_ropes<const D: usize>(moves: &[Move<D>]) -> Result<()> {
+  for knots in 1..=100 {
+    let got = simulate(moves, knots).visited.len();
+    let expected = reference_visits(moves, knots);
+    if got != expected {
+      return Err(format!("{} knots: visited {}, expected {}", knots, got, expected).into());
+    }
+  }
 
-  writeln!(io::stdout(), "{:?}", res)?;
+  writeln!(io::stdout(), "ok, 1 to 100 knots match")?;
   Ok(())
 }
 
-// -------------- helpers -------------------
+// draws the rope the way the puzzle does, as text or as a terminal animation,
+// and/or the cells the tail visited. the viewport is sized to fit every knot
+// over the whole run, so frames don't jump around.
+fn show_motion(moves: &[Move<2>], knots: usize, opts: &Options) -> Result<()> {
+  let start = Position::origin();
+  let mut view = Viewport {
+    min: start,
+    max: start,
+  };
+  let mut sim = RopeSim::new(moves, knots);
+  for snapshot in sim.by_ref() {
+    snapshot.knots.iter().for_each(|&knot| view.include(knot));
+  }
+  let rope = sim.into_rope();
 
-fn move_tail(
-  rope_state_tail: &mut Position,
-  rope_state_head: &mut Position,
-  rope_state_visited: &mut BTreeSet<String>,
-) {
-  match (
-    rope_state_head.x - rope_state_tail.x,
-    rope_state_head.y - rope_state_tail.y,
-  ) {
-    (0, 2) => {
-      rope_state_tail.y += 1;
-      rope_state_visited.insert(format!("[{:?},{:?}]", rope_state_tail.x, rope_state_tail.y));
-    }
-    (-1, 2) | (-2, 2) | (-2, 1) => {
-      rope_state_tail.x -= 1;
-      rope_state_tail.y += 1;
-      rope_state_visited.insert(format!("[{:?},{:?}]", rope_state_tail.x, rope_state_tail.y));
-    }
+  let mut out = BufWriter::new(io::stdout());
+  let mode = match (opts.render, opts.animate) {
+    (Some(mode), _) => mode,
+    (None, true) => RenderMode::Steps,
+    (None, false) => return print_visited(&mut out, &rope, &view),
+  };
+  let frame_time = Duration::from_secs_f64(1.0 / opts.fps.max(0.001));
 
-    (-2, 0) => {
-      rope_state_tail.x -= 1;
-      rope_state_visited.insert(format!("[{:?},{:?}]", rope_state_tail.x, rope_state_tail.y));
+  if opts.animate {
+    // clear the screen and hide the cursor, every frame then starts top left.
+    write!(out, "\x1b[2J\x1b[?25l")?;
+  } else {
+    writeln!(
+      out,
+      "== Initial State ==\n\n{}\n",
+      render_rope(&Rope::new(knots).knots, &view)
+    )?;
+  }
+
+  for snapshot in RopeSim::new(moves, knots) {
+    let m = &moves[snapshot.instruction];
+    if mode == RenderMode::Moves && snapshot.instruction_step != m.steps {
+      continue;
     }
-    (-2, -1) | (-2, -2) | (-1, -2) => {
-      rope_state_tail.x -= 1;
-      rope_state_tail.y -= 1;
-      rope_state_visited.insert(format!("[{:?},{:?}]", rope_state_tail.x, rope_state_tail.y));
+    let frame = render_rope(&snapshot.knots, &view);
+    match opts.animate {
+      true => {
+        write!(out, "\x1b[H{}", frame)?;
+        out.flush()?;
+        thread::sleep(frame_time);
+      }
+      // in step mode the move is named once, above its first step.
+      false if mode == RenderMode::Moves || snapshot.instruction_step == 1 => {
+        writeln!(out, "== {} {} ==\n\n{}\n", m.name, m.steps, frame)?
+      }
+      false => writeln!(out, "{}\n", frame)?,
     }
+  }
+
+  if opts.animate {
+    writeln!(out, "\x1b[?25h")?;
+  }
+  if opts.map {
+    print_visited(&mut out, &rope, &view)?;
+  }
+  Ok(())
+}
+
+fn print_visited(out: &mut impl Write, rope: &Rope<2>, view: &Viewport<2>) -> Result<()> {
+  let start = Position::origin();
+  let map = view.draw(|pos| match pos {
+    _ if pos == start => 's',
+    _ if rope.visited.contains(&pos) => '#',
+    _ => '.',
+  });
+  writeln!(out, "{}", map)?;
+  Ok(())
+}
+
+// where every knot is after `step` steps, and which move got it there.
+fn print_snapshot<const D: usize>(moves: &[Move<D>], knots: usize, step: u64) -> Result<()> {
+  let mut sim = RopeSim::new(moves, knots);
+  let snapshot = sim
+    .seek(step)
+    .ok_or_else(|| format!("the moves only take {} steps", sim.step))?;
+
+  let mut out = io::stdout();
+  match moves.get(snapshot.instruction) {
+    Some(m) if snapshot.step > 0 => writeln!(
+      out,
+      "step {}: move {} ({} {}), step {} of {}",
+      snapshot.step,
+      snapshot.instruction + 1,
+      m.name,
+      m.steps,
+      snapshot.instruction_step,
+      m.steps
+    )?,
+    _ => writeln!(out, "step 0: initial state")?,
+  }
+  for (idx, knot) in snapshot.knots.iter().enumerate() {
+    writeln!(out, "{:>3}: {}", idx, knot.coords())?;
+  }
+  writeln!(out, "tail visited {}", sim.rope().visited.len())?;
+  Ok(())
+}
+
+// coordinates take one column per axis, eg. `x,y` or `min_x,min_y,min_z`.
+fn write_csv<const D: usize>(stats: &[KnotStats<D>], table: CsvTable) -> Result<()> {
+  let axes = |prefix: &str| {
+    ["x", "y", "z"][..D]
+      .iter()
+      .map(|axis| format!("{}{}", prefix, axis))
+      .collect::<Vec<_>>()
+      .join(",")
+  };
 
-    (0, -2) => {
-      rope_state_tail.y -= 1;
-      rope_state_visited.insert(format!("[{:?},{:?}]", rope_state_tail.x, rope_state_tail.y));
+  let mut out = BufWriter::new(io::stdout());
+  match table {
+    CsvTable::Stats => {
+      writeln!(
+        out,
+        "knot,visited,{},{},distance",
+        axes("min_"),
+        axes("max_")
+      )?;
+      for (knot, stat) in stats.iter().enumerate() {
+        writeln!(
+          out,
+          "{},{},{},{},{}",
+          knot,
+          stat.visited.len(),
+          stat.bounds.min.coords(),
+          stat.bounds.max.coords(),
+          stat.distance
+        )?;
+      }
+    }
+    CsvTable::Visits => {
+      writeln!(out, "knot,{}", axes(""))?;
+      for (knot, stat) in stats.iter().enumerate() {
+        let mut cells = stat.visited.iter().collect::<Vec<_>>();
+        cells.sort();
+        for pos in cells {
+          writeln!(out, "{},{}", knot, pos.coords())?;
+        }
+      }
     }
-    (1, -2) | (2, -2) | (2, -1) => {
-      rope_state_tail.x += 1;
-      rope_state_tail.y -= 1;
-      rope_state_visited.insert(format!("[{:?},{:?}]", rope_state_tail.x, rope_state_tail.y));
+    CsvTable::Shared => {
+      let mut cells = stats[0]
+        .visited
+        .iter()
+        .filter(|pos| stats.iter().all(|stat| stat.visited.contains(pos)))
+        .collect::<Vec<_>>();
+      cells.sort();
+      writeln!(out, "{}", axes(""))?;
+      for pos in cells {
+        writeln!(out, "{}", pos.coords())?;
+      }
     }
+  }
+  out.flush()?;
+  Ok(())
+}
+
+// -------------- helpers -------------------
+
+// replays the moves and keeps track of every knot, not just the tail.
+fn knot_stats<const D: usize>(moves: &[Move<D>], knots: usize) -> Vec<KnotStats<D>> {
+  let start = Position::origin();
+  let mut stats = vec![
+    KnotStats {
+      visited: HashSet::from([start]),
+      bounds: Viewport {
+        min: start,
+        max: start,
+      },
+      distance: 0,
+    };
+    knots.max(1)
+  ];
+  let mut last = vec![start; knots.max(1)];
 
-    (2, 0) => {
-      rope_state_tail.x += 1;
-      rope_state_visited.insert(format!("[{:?},{:?}]", rope_state_tail.x, rope_state_tail.y));
+  for snapshot in RopeSim::new(moves, knots) {
+    for (idx, &knot) in snapshot.knots.iter().enumerate() {
+      if knot == last[idx] {
+        continue;
+      }
+      let stat = &mut stats[idx];
+      stat.distance += knot.distance(&last[idx]) as u64;
+      stat.visited.insert(knot);
+      stat.bounds.include(knot);
+      last[idx] = knot;
     }
-    (2, 1) | (2, 2) | (1, 2) => {
-      rope_state_tail.x += 1;
-      rope_state_tail.y += 1;
-      rope_state_visited.insert(format!("[{:?},{:?}]", rope_state_tail.x, rope_state_tail.y));
+  }
+  stats
+}
+
+fn simulate<const D: usize>(moves: &[Move<D>], knots: usize) -> Rope<D> {
+  let mut sim = RopeSim::new(moves, knots);
+  while sim.advance() {}
+  sim.into_rope()
+}
+
+// the puzzle's notation: `H` for the head, `T` for the tail of a two knot
+// rope, otherwise the knot's number. a knot hides the ones behind it, and `s`
+// marks the start when nothing covers it.
+fn render_rope(knots: &[Position<2>], view: &Viewport<2>) -> String {
+  let start = Position::origin();
+  view.draw(|pos| match knots.iter().position(|&knot| knot == pos) {
+    Some(0) => 'H',
+    Some(1) if knots.len() == 2 => 'T',
+    Some(idx) => char::from_digit(idx as u32, 36).unwrap_or('+'),
+    None if pos == start => 's',
+    None => '.',
+  })
+}
+
+// the plainest possible simulation, knots as arrays and the whole rope moved
+// every step, to check `Rope` against.
+fn reference_visits<const D: usize>(moves: &[Move<D>], knots: usize) -> usize {
+  let mut rope = vec![[0i32; D]; knots.max(1)];
+  let mut visited = HashSet::from([[0i32; D]]);
+  for m in moves {
+    for _ in 0..m.steps {
+      for (c, d) in rope[0].iter_mut().zip(m.delta.0) {
+        *c += d;
+      }
+      for i in 1..rope.len() {
+        let gap: [i32; D] = std::array::from_fn(|axis| rope[i - 1][axis] - rope[i][axis]);
+        if gap.iter().any(|g| g.abs() > 1) {
+          for (c, g) in rope[i].iter_mut().zip(gap) {
+            *c += g.signum();
+          }
+        }
+      }
+      visited.insert(rope[rope.len() - 1]);
     }
-    _ => {}
   }
+  visited.len()
 }
 
-fn move_knots(rope_vec: &mut [&mut Rope]) {
-  // boring offsetting stuff.
-  (0..rope_vec.len() - 1).for_each(|idx| {
-    let mut visited_idx = 0;
-    if idx == rope_vec.len() - 2 {
-      visited_idx = rope_vec.len() - 1;
-    };
+// while the tail doesn't touch the head it moves one step straight towards
+// it, diagonally if they're not lined up. a gap of any size is closed one step
+// per call, in 2D or 3D alike.
+fn move_tail<const D: usize>(tail: &mut Position<D>, head: Position<D>) -> bool {
+  if tail.distance(&head) <= 1 {
+    return false;
+  }
+  for (c, h) in tail.0.iter_mut().zip(head.0) {
+    *c += (h - *c).signum();
+  }
+  true
+}
 
-    move_tail(
-      &mut rope_vec[idx + 1].head,
-      &mut rope_vec[idx].tail,
-      &mut rope_vec[0].visited,
-    );
-
-    move_tail(
-      &mut rope_vec[idx + 1].tail,
-      &mut rope_vec[idx + 1].head,
-      &mut rope_vec[visited_idx].visited,
-    );
-  });
+// moves of 1 to 20 steps along a random axis.
+fn random_moves<const D: usize>(count: usize, rng: &mut Rng) -> Vec<Move<D>> {
+  let letters = ["R", "L", "U", "D", "F", "B"];
+  (0..count)
+    .map(|_| {
+      let letter = letters[rng.below(2 * D as u64) as usize];
+      let steps = 1 + rng.below(20);
+      parse(&format!("{} {}", letter, steps)).unwrap()
+    })
+    .collect()
+}
+
+// xorshift, good enough for making up moves.
+struct Rng(u64);
+
+impl Rng {
+  fn below(&mut self, n: u64) -> u64 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    self.0 % n
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn moves<const D: usize>(input: &str) -> Vec<Move<D>> {
+    input.lines().map(|line| parse(line).unwrap()).collect()
+  }
+
+  fn visits<const D: usize>(moves: &[Move<D>], knots: usize) -> usize {
+    simulate(moves, knots).visited.len()
+  }
+
+  // the first five moves of the puzzle's example, worked out by hand.
+  #[test]
+  fn sample() {
+    let moves = moves::<2>(include_str!("../input/test.txt"));
+    assert_eq!((visits(&moves, 2), visits(&moves, 10)), (9, 1));
+    verify_ropes(&moves).unwrap();
+  }
+
+  #[test]
+  fn larger_sample() {
+    let moves = moves::<2>(include_str!("../input/test2.txt"));
+    assert_eq!((visits(&moves, 2), visits(&moves, 10)), (88, 36));
+    verify_ropes(&moves).unwrap();
+  }
+
+  #[test]
+  fn random_moves_match() {
+    verify_ropes(&random_moves::<2>(300, &mut Rng(42 | 1))).unwrap();
+    verify_ropes(&random_moves::<3>(300, &mut Rng(42 | 1))).unwrap();
+  }
 }
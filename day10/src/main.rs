@@ -3,171 +3,197 @@ use std::io::{self, Read, Write};
 
 type Result<T> = ::std::result::Result<T, Box<dyn Error>>;
 
-#[derive(Debug, PartialEq)]
-enum Instructions {
+const CRT_WIDTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Instruction {
   Noop,
   AddX(i32),
-  Done,
 }
 
+// the state of the CPU during one cycle, before the instruction executing in
+// it has finished.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cycle {
+  number: usize, // the first cycle is 1.
+  pc: usize,     // the instruction being executed.
+  x: i32,
+}
+
+#[derive(Debug)]
+struct Cpu<'a> {
+  program: &'a [Instruction],
+  pc: usize,
+  x: i32,
+  cycle: usize,
+  busy: usize, // cycles already spent on the instruction at `pc`.
+}
+
+// anything that wants to look at every cycle, see `run`.
+trait CycleObserver {
+  fn observe(&mut self, cycle: &Cycle);
+}
+
+// adds up `cycle * x` at the given cycles.
+#[derive(Debug)]
+struct SignalSampler {
+  at: Vec<usize>,
+  total: i32,
+}
+
+// draws one pixel per cycle, lit when the 3 pixel wide sprite centered on `x`
+// covers the column being drawn.
 #[derive(Debug)]
-struct Processor {
-  x_register: i32,
-  stack: Vec<(Instructions, i32)>,
-  signal_strength: Vec<i32>,
-}
-
-impl Processor {
-  fn new() -> Processor {
-    Processor {
-      x_register: 1,
-      stack: vec![],
-      signal_strength: vec![],
+struct Crt {
+  pixels: String,
+  column: usize,
+}
+
+impl Instruction {
+  fn cycles(&self) -> usize {
+    match self {
+      Instruction::Noop => 1,
+      Instruction::AddX(_) => 2,
+    }
+  }
+}
+
+impl<'a> Cpu<'a> {
+  fn new(program: &'a [Instruction]) -> Self {
+    Cpu {
+      program,
+      pc: 0,
+      x: 1,
+      cycle: 0,
+      busy: 0,
+    }
+  }
+
+  // runs exactly one cycle and returns what the CPU looked like during it,
+  // none once the program has ended. an instruction's effect on the register
+  // only shows from the cycle after its last one.
+  fn tick(&mut self) -> Option<Cycle> {
+    let instruction = *self.program.get(self.pc)?;
+    self.cycle += 1;
+    let during = Cycle {
+      number: self.cycle,
+      pc: self.pc,
+      x: self.x,
+    };
+
+    self.busy += 1;
+    if self.busy == instruction.cycles() {
+      if let Instruction::AddX(val) = instruction {
+        self.x += val;
+      }
+      self.pc += 1;
+      self.busy = 0;
     }
+    Some(during)
   }
+}
+
+impl Iterator for Cpu<'_> {
+  type Item = Cycle;
 
-  fn countdown(&mut self) -> Vec<usize> {
-    let remove_index = self
-      .stack
-      .iter_mut()
-      .enumerate()
-      .skip_while(|(_, (x, _))| *x == Instructions::Done)
-      .filter_map(|(idx, (_, ref mut tick))| {
-        *tick -= 1;
-        if *tick == 0 {
-          Some(idx)
-        } else {
-          None
-        }
-      })
-      .collect::<Vec<usize>>();
-    remove_index
+  fn next(&mut self) -> Option<Cycle> {
+    self.tick()
   }
+}
 
-  fn collect_signal(&mut self, cycles: &i32) {
-    let nth_signal = [20, 60, 100, 140, 180, 220];
-    if let Some(nth) = nth_signal.iter().find(|nth| *nth == cycles) {
-      self.signal_strength.push(self.x_register * nth)
+impl SignalSampler {
+  fn new(at: &[usize]) -> Self {
+    SignalSampler {
+      at: at.to_vec(),
+      total: 0,
     }
   }
 }
 
-fn parse(line: &str) -> Instructions {
+impl CycleObserver for SignalSampler {
+  fn observe(&mut self, cycle: &Cycle) {
+    if self.at.contains(&cycle.number) {
+      self.total += cycle.number as i32 * cycle.x;
+    }
+  }
+}
+
+impl Crt {
+  fn new() -> Self {
+    Crt {
+      pixels: String::new(),
+      column: 0,
+    }
+  }
+}
+
+impl CycleObserver for Crt {
+  fn observe(&mut self, cycle: &Cycle) {
+    match (cycle.x - self.column as i32).abs() <= 1 {
+      true => self.pixels.push('#'),
+      false => self.pixels.push(' '),
+    };
+
+    self.column += 1;
+    if self.column == CRT_WIDTH {
+      self.pixels.push('\n');
+      self.column = 0;
+    }
+  }
+}
+
+fn parse(line: &str) -> Result<Instruction> {
   let l = line.split(' ').collect::<Vec<_>>();
 
-  match l[0] {
-    "noop" => Instructions::Noop,
-    "addx" => Instructions::AddX(l[1].parse::<i32>().unwrap()),
-    _ => Instructions::Done,
+  match l[..] {
+    ["noop"] => Ok(Instruction::Noop),
+    ["addx", val] => Ok(Instruction::AddX(val.parse::<i32>()?)),
+    _ => Err(format!("unknown instruction: {}", line).into()),
   }
 }
 
 fn main() -> Result<()> {
   let mut input = String::new();
   io::stdin().read_to_string(&mut input)?;
-  solve_part1(&input)?;
-  solve_part2(&input)?;
+
+  let program = input
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty())
+    .map(|(idx, line)| parse(line.trim()).map_err(|e| format!("line {}: {}", idx + 1, e).into()))
+    .collect::<Result<Vec<_>>>()?;
+
+  let mut sampler = SignalSampler::new(&[20, 60, 100, 140, 180, 220]);
+  let mut crt = Crt::new();
+  run(&program, &mut [&mut sampler, &mut crt]);
+
+  solve_part1(&sampler)?;
+  solve_part2(&crt)?;
   Ok(())
 }
 
 // Find the signal strength during the 20th, 60th, 100th, 140th, 180th, and 220th cycles.
 // What is the sum of these six signal strengths?
-fn solve_part1(input: &str) -> Result<()> {
-  let mut cycles = 1;
-  let mut processor = Processor::new();
-
-  input.lines().for_each(|line| {
-    match parse(line) {
-      Instructions::Noop => processor.stack.push((Instructions::Noop, 1)),
-      Instructions::AddX(val) => processor.stack.push((Instructions::AddX(val), 2)),
-      Instructions::Done => {}
-    };
-
-    let marked_done_idx = processor.countdown();
-
-    marked_done_idx.iter().for_each(|idx| {
-      let register_add_value = &processor.stack[*idx];
-      match register_add_value.0 {
-        Instructions::AddX(val) => {
-          (0..2).for_each(|_| {
-            processor.collect_signal(&cycles);
-            cycles += 1;
-          });
-          processor.x_register += val;
-        }
-        Instructions::Noop => {
-          processor.collect_signal(&cycles);
-          cycles += 1;
-        }
-        Instructions::Done => {}
-      }
-      processor.stack[*idx] = (Instructions::Done, 0);
-    });
-  });
-
-  let res: i32 = processor.signal_strength.iter().sum();
-  writeln!(io::stdout(), "{:?}", res)?;
+fn solve_part1(sampler: &SignalSampler) -> Result<()> {
+  writeln!(io::stdout(), "{:?}", sampler.total)?;
   Ok(())
 }
 
 // Render the image given by your program. What eight capital letters appear on your CRT?
-fn solve_part2(input: &str) -> Result<()> {
-  let mut cycles = 1;
-  let mut processor = Processor::new();
-  let mut sprite_pos = [0, 1, 2];
-  let mut crt_pos = 0;
-  let mut crt_pixels: String = String::new();
-
-  input.lines().for_each(|line| {
-    match parse(line) {
-      Instructions::Noop => processor.stack.push((Instructions::Noop, 1)),
-      Instructions::AddX(val) => processor.stack.push((Instructions::AddX(val), 2)),
-      Instructions::Done => {}
-    };
+fn solve_part2(crt: &Crt) -> Result<()> {
+  writeln!(io::stdout(), "{}", crt.pixels)?;
+  Ok(())
+}
 
-    let marked_done_idx = processor.countdown();
-
-    marked_done_idx.iter().for_each(|idx| {
-      let register_add_value = &processor.stack[*idx];
-      match register_add_value.0 {
-        Instructions::AddX(val) => {
-          (0..2).for_each(|_| {
-            processor.collect_signal(&cycles);
-            match sprite_pos.contains(&crt_pos) {
-              true => crt_pixels.push('#'),
-              false => crt_pixels.push(' '),
-            };
-
-            if cycles % 40 == 0 {
-              crt_pixels.push('\n');
-              sprite_pos.iter_mut().for_each(|pos| *pos += 40);
-            }
-            cycles += 1;
-            crt_pos += 1;
-          });
-          sprite_pos.iter_mut().for_each(|pos| *pos += val);
-          processor.x_register += val;
-        }
-        Instructions::Noop => {
-          processor.collect_signal(&cycles);
-          match sprite_pos.contains(&crt_pos) {
-            true => crt_pixels.push('#'),
-            false => crt_pixels.push(' '),
-          };
-
-          if cycles % 40 == 0 {
-            crt_pixels.push('\n');
-            sprite_pos.iter_mut().for_each(|pos| *pos += 40);
-          }
-          cycles += 1;
-          crt_pos += 1;
-        }
-        Instructions::Done => {}
-      }
-      processor.stack[*idx] = (Instructions::Done, 0);
-    });
-  });
+// ---------------------- UTILS -------------------------
 
-  println!("{}", crt_pixels);
-  Ok(())
+// runs the program to the end, showing every cycle to every observer.
+fn run(program: &[Instruction], observers: &mut [&mut dyn CycleObserver]) {
+  for cycle in Cpu::new(program) {
+    for observer in observers.iter_mut() {
+      observer.observe(&cycle);
+    }
+  }
 }
+
+// ---------------------------------------------------------